@@ -1,7 +1,25 @@
 #[allow(unused)]
 
 pub const USER_STACK_SIZE: usize = 4096 * 5;
+/// Lowest address picked by mmap when the user does not give one.
+pub const MMAP_BASE: usize = 0x1000_0000;
+/// Signal handlers return to this address, which is never mapped, so that
+/// the page fault makes them sigreturn.
+pub const SIGRETURN_TRAMPOLINE: usize = 0x1000;
+/// User mappings lie in `[USER_SPACE_BOTTOM, USER_SPACE_TOP)`, above page 0
+/// and the trampoline page and below the kernel half of every architecture.
+pub const USER_SPACE_BOTTOM: usize = SIGRETURN_TRAMPOLINE + PAGE_SIZE;
+pub const USER_SPACE_TOP: usize = 0x40_0000_0000;
 pub const KERNEL_STACK_SIZE: usize = 4096 * 5;
 pub const KERNEL_HEAP_SIZE: usize = 0x200_0000;
 
 pub const PAGE_SIZE: usize = 0x1000;
+/// Maximum number of harts the scheduler runs on.
+pub const MAX_HARTS: usize = 8;
+
+/// Timer interrupts per second, the rate at which polyhal programs the timer.
+pub const TICKS_PER_SEC: usize = 100;
+/// Time slice of a nice 0 task in ticks. Heavier tasks get longer slices.
+pub const TIME_SLICE_TICKS: usize = 2;
+/// Longest time slice in ticks.
+pub const MAX_TIME_SLICE_TICKS: usize = 10;
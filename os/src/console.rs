@@ -1,9 +1,13 @@
 use core::fmt::{self, Write};
 
 use polyhal::debug_console::DebugConsole;
+use polyhal::utils::MutexNoIrq;
 
 struct Stdout;
 
+/// Keep lines printed by different harts from interleaving.
+static PRINT_LOCK: MutexNoIrq<()> = MutexNoIrq::new(());
+
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> fmt::Result {
         for c in s.bytes() {
@@ -14,9 +18,18 @@ impl Write for Stdout {
 }
 
 pub fn print(args: fmt::Arguments) {
+    let _guard = PRINT_LOCK.lock();
     Stdout.write_fmt(args).unwrap();
 }
 
+/// Print raw bytes which may not be UTF-8, such as the echo of console input.
+pub fn print_bytes(bytes: &[u8]) {
+    let _guard = PRINT_LOCK.lock();
+    for c in bytes {
+        DebugConsole::putchar(*c);
+    }
+}
+
 #[macro_export]
 macro_rules! print {
     ($fmt: literal $(, $($arg: tt)+)?) => {
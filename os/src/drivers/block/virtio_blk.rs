@@ -2,12 +2,12 @@ use core::ptr::NonNull;
 
 use super::BlockDevice;
 use crate::mm::{frame_alloc, frame_dealloc, frames_alloc, FrameTracker};
-use crate::sync::UPSafeCell;
 use alloc::vec::Vec;
 use lazy_static::*;
 use log::debug;
 use polyhal::consts::VIRT_ADDR_START;
 use polyhal::pagetable::PAGE_SIZE;
+use polyhal::utils::MutexNoIrq;
 use polyhal::PhysAddr;
 use virtio_drivers::device::blk::VirtIOBlk;
 use virtio_drivers::transport::mmio::{MmioTransport, VirtIOHeader};
@@ -20,10 +20,10 @@ const VIRTIO0: PhysAddr = polyhal::pa!(0x10001000);
 #[cfg(target_arch = "aarch64")]
 const VIRTIO0: PhysAddr = polyhal::pa!(0xa00_0000);
 
-pub struct VirtIOBlock(UPSafeCell<VirtIOBlk<VirtioHal, MmioTransport>>);
+pub struct VirtIOBlock(MutexNoIrq<VirtIOBlk<VirtioHal, MmioTransport>>);
 
 lazy_static! {
-    static ref QUEUE_FRAMES: UPSafeCell<Vec<FrameTracker>> = unsafe { UPSafeCell::new(Vec::new()) };
+    static ref QUEUE_FRAMES: MutexNoIrq<Vec<FrameTracker>> = MutexNoIrq::new(Vec::new());
 }
 
 unsafe impl Sync for VirtIOBlock {}
@@ -32,13 +32,13 @@ unsafe impl Send for VirtIOBlock {}
 impl BlockDevice for VirtIOBlock {
     fn read_block(&self, block_id: usize, buf: &mut [u8]) {
         self.0
-            .exclusive_access()
+            .lock()
             .read_blocks(block_id, buf)
             .expect("Error when reading VirtIOBlk");
     }
     fn write_block(&self, block_id: usize, buf: &[u8]) {
         self.0
-            .exclusive_access()
+            .lock()
             .write_blocks(block_id, buf)
             .expect("Error when writing VirtIOBlk");
     }
@@ -48,7 +48,7 @@ impl VirtIOBlock {
     #[allow(unused)]
     pub fn new() -> Self {
         unsafe {
-            Self(UPSafeCell::new(
+            Self(MutexNoIrq::new(
                 VirtIOBlk::<VirtioHal, MmioTransport>::new(
                     MmioTransport::new(NonNull::new_unchecked(VIRTIO0.get_mut_ptr()))
                         .expect("this is not a valid virtio device"),
@@ -72,12 +72,12 @@ unsafe impl Hal for VirtioHal {
         //         paddr = frame.paddr;
         //     };
         //     assert_eq!(frame.paddr.raw(), paddr.raw() + PAGE_SIZE);
-        //     QUEUE_FRAMES.exclusive_access().push(frame);
+        //     QUEUE_FRAMES.lock().push(frame);
         // }
         // unsafe { (paddr.raw(), NonNull::new_unchecked(paddr.get_mut_ptr())) }
         let frames = frames_alloc(pages).unwrap();
         let paddr = frames[0].paddr;
-        QUEUE_FRAMES.exclusive_access().extend(frames);
+        QUEUE_FRAMES.lock().extend(frames);
         unsafe { (paddr.raw(), NonNull::new_unchecked(paddr.get_mut_ptr())) }
     }
 
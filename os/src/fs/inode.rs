@@ -1,16 +1,17 @@
 use super::File;
 use crate::drivers::BLOCK_DEVICE;
-use crate::sync::UPSafeCell;
+use crate::syscall::Errno;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 use bitflags::*;
 use easyfs::{EasyFileSystem, Inode};
 use lazy_static::*;
+use polyhal::utils::MutexNoIrq;
 
 pub struct OSInode {
     readable: bool,
     writable: bool,
-    inner: UPSafeCell<OSInodeInner>,
+    inner: MutexNoIrq<OSInodeInner>,
 }
 
 pub struct OSInodeInner {
@@ -23,11 +24,11 @@ impl OSInode {
         Self {
             readable,
             writable,
-            inner: unsafe { UPSafeCell::new(OSInodeInner { offset: 0, inode }) },
+            inner: MutexNoIrq::new(OSInodeInner { offset: 0, inode }),
         }
     }
     pub fn read_all(&self) -> Vec<u8> {
-        let mut inner = self.inner.exclusive_access();
+        let mut inner = self.inner.lock();
         let mut buffer = [0u8; 512];
         let mut v: Vec<u8> = Vec::new();
         loop {
@@ -111,16 +112,16 @@ impl File for OSInode {
     fn writable(&self) -> bool {
         self.writable
     }
-    fn read(&self, buf: &mut [u8]) -> usize {
-        let mut inner = self.inner.exclusive_access();
+    fn read(&self, buf: &mut [u8]) -> Result<usize, Errno> {
+        let mut inner = self.inner.lock();
         let rsize = inner.inode.read_at(inner.offset, buf);
         inner.offset += rsize;
-        rsize
+        Ok(rsize)
     }
-    fn write(&self, buf: &mut [u8]) -> usize {
-        let mut inner = self.inner.exclusive_access();
+    fn write(&self, buf: &mut [u8]) -> Result<usize, Errno> {
+        let mut inner = self.inner.lock();
         let wsize = inner.inode.write_at(inner.offset, buf);
         inner.offset += wsize;
-        wsize
+        Ok(wsize)
     }
 }
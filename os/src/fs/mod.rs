@@ -1,14 +1,22 @@
 mod inode;
 mod pipe;
 mod stdio;
+mod tty;
+
+use crate::syscall::{Errno, SysResult};
 
 pub trait File: Send + Sync {
     fn readable(&self) -> bool;
     fn writable(&self) -> bool;
-    fn read(&self, buf: &mut [u8]) -> usize;
-    fn write(&self, buf: &mut [u8]) -> usize;
+    fn read(&self, buf: &mut [u8]) -> Result<usize, Errno>;
+    fn write(&self, buf: &mut [u8]) -> Result<usize, Errno>;
+    /// Device specific requests, only terminals have some.
+    fn ioctl(&self, _request: usize, _arg: usize) -> SysResult {
+        Err(Errno::ENOTTY)
+    }
 }
 
 pub use inode::{list_apps, open_file, OpenFlags};
 pub use pipe::make_pipe;
 pub use stdio::{Stdin, Stdout};
+pub use tty::poll_stdin;
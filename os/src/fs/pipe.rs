@@ -1,24 +1,34 @@
 use super::File;
-use crate::sync::UPSafeCell;
+use crate::sync::WaitQueue;
+use crate::syscall::Errno;
+use crate::task::{current_fatal_pending, current_signal_pending};
 use alloc::sync::{Arc, Weak};
-
-use crate::task::suspend_current_and_run_next;
+use polyhal::utils::MutexNoIrq;
 
 pub struct Pipe {
     readable: bool,
     writable: bool,
-    buffer: Arc<UPSafeCell<PipeRingBuffer>>,
+    buffer: Arc<PipeBuffer>,
+}
+
+/// The ring buffer shared by both ends of a pipe, and the tasks blocked on it.
+pub struct PipeBuffer {
+    ring: MutexNoIrq<PipeRingBuffer>,
+    // readers waiting for data or the close of the write end
+    read_queue: WaitQueue,
+    // writers waiting for free space
+    write_queue: WaitQueue,
 }
 
 impl Pipe {
-    pub fn read_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
+    pub fn read_end_with_buffer(buffer: Arc<PipeBuffer>) -> Self {
         Self {
             readable: true,
             writable: false,
             buffer,
         }
     }
-    pub fn write_end_with_buffer(buffer: Arc<UPSafeCell<PipeRingBuffer>>) -> Self {
+    pub fn write_end_with_buffer(buffer: Arc<PipeBuffer>) -> Self {
         Self {
             readable: false,
             writable: true,
@@ -97,13 +107,27 @@ impl PipeRingBuffer {
 
 /// Return (read_end, write_end)
 pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
-    let buffer = Arc::new(unsafe { UPSafeCell::new(PipeRingBuffer::new()) });
+    let buffer = Arc::new(PipeBuffer {
+        ring: MutexNoIrq::new(PipeRingBuffer::new()),
+        read_queue: WaitQueue::new(),
+        write_queue: WaitQueue::new(),
+    });
     let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone()));
     let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone()));
-    buffer.exclusive_access().set_write_end(&write_end);
+    buffer.ring.lock().set_write_end(&write_end);
     (read_end, write_end)
 }
 
+/// The result of a transfer a signal has interrupted after `len` bytes: the
+/// bytes done so far, or a restart if there are none.
+fn interrupted(len: usize) -> Result<usize, Errno> {
+    if len > 0 {
+        Ok(len)
+    } else {
+        Err(Errno::ERESTARTSYS)
+    }
+}
+
 impl File for Pipe {
     fn readable(&self) -> bool {
         self.readable
@@ -111,60 +135,77 @@ impl File for Pipe {
     fn writable(&self) -> bool {
         self.writable
     }
-    fn read(&self, buf: &mut [u8]) -> usize {
+    fn read(&self, buf: &mut [u8]) -> Result<usize, Errno> {
         assert!(self.readable());
         let want_to_read = buf.len();
         let mut buf_iter = buf.into_iter();
         let mut already_read = 0usize;
         loop {
-            let mut ring_buffer = self.buffer.exclusive_access();
+            let mut ring_buffer = self.buffer.ring.lock();
             let loop_read = ring_buffer.available_read();
             if loop_read == 0 {
                 if ring_buffer.all_write_ends_closed() {
-                    return already_read;
+                    return Ok(already_read);
                 }
-                drop(ring_buffer);
-                suspend_current_and_run_next();
+                if current_fatal_pending() || current_signal_pending() {
+                    return interrupted(already_read);
+                }
+                self.buffer.read_queue.wait_with(ring_buffer);
                 continue;
             }
+            // there will be free space for the writers
+            self.buffer.write_queue.wake_all();
             for _ in 0..loop_read {
                 if let Some(byte_ref) = buf_iter.next() {
                     *byte_ref = ring_buffer.read_byte();
                     already_read += 1;
                     if already_read == want_to_read {
-                        return want_to_read;
+                        return Ok(want_to_read);
                     }
                 } else {
-                    return already_read;
+                    return Ok(already_read);
                 }
             }
         }
     }
-    fn write(&self, buf: &mut [u8]) -> usize {
+    fn write(&self, buf: &mut [u8]) -> Result<usize, Errno> {
         assert!(self.writable());
         let want_to_write = buf.len();
         let mut buf_iter = buf.into_iter();
         let mut already_write = 0usize;
         loop {
-            let mut ring_buffer = self.buffer.exclusive_access();
+            let mut ring_buffer = self.buffer.ring.lock();
             let loop_write = ring_buffer.available_write();
             if loop_write == 0 {
-                drop(ring_buffer);
-                suspend_current_and_run_next();
+                if current_fatal_pending() || current_signal_pending() {
+                    return interrupted(already_write);
+                }
+                self.buffer.write_queue.wait_with(ring_buffer);
                 continue;
             }
+            // there will be data for the readers
+            self.buffer.read_queue.wake_all();
             // write at most loop_write bytes
             for _ in 0..loop_write {
                 if let Some(byte_ref) = buf_iter.next() {
-                    ring_buffer.write_byte( *byte_ref );
+                    ring_buffer.write_byte(*byte_ref);
                     already_write += 1;
                     if already_write == want_to_write {
-                        return want_to_write;
+                        return Ok(want_to_write);
                     }
                 } else {
-                    return already_write;
+                    return Ok(already_write);
                 }
             }
         }
     }
 }
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        if self.writable {
+            // readers blocked on an empty pipe will see the end of file
+            self.buffer.read_queue.wake_all();
+        }
+    }
+}
@@ -1,7 +1,6 @@
-use polyhal::debug_console::DebugConsole;
-
+use super::tty::{tty_ioctl, tty_read};
 use super::File;
-use crate::task::suspend_current_and_run_next;
+use crate::syscall::{Errno, SysResult};
 
 pub struct Stdin;
 
@@ -14,23 +13,15 @@ impl File for Stdin {
     fn writable(&self) -> bool {
         false
     }
-    fn read(&self, user_buf: &mut [u8]) -> usize {
-        assert_eq!(user_buf.len(), 1);
-        // busy loop
-        let c: u8;
-        loop {
-            if let Some(ch) = DebugConsole::getchar() {
-                c = ch;
-                break;
-            }
-            suspend_current_and_run_next();
-        }
-        user_buf[0] = c as u8;
-        1
-    }
-    fn write(&self, _user_buf: &mut [u8]) -> usize {
+    fn read(&self, user_buf: &mut [u8]) -> Result<usize, Errno> {
+        tty_read(user_buf)
+    }
+    fn write(&self, _user_buf: &mut [u8]) -> Result<usize, Errno> {
         panic!("Cannot write to stdin!");
     }
+    fn ioctl(&self, request: usize, arg: usize) -> SysResult {
+        tty_ioctl(request, arg)
+    }
 }
 
 impl File for Stdout {
@@ -40,14 +31,17 @@ impl File for Stdout {
     fn writable(&self) -> bool {
         true
     }
-    fn read(&self, _user_buf: &mut [u8]) -> usize {
+    fn read(&self, _user_buf: &mut [u8]) -> Result<usize, Errno> {
         panic!("Cannot read from stdout!");
     }
-    fn write(&self, user_buf: &mut [u8]) -> usize {
+    fn write(&self, user_buf: &mut [u8]) -> Result<usize, Errno> {
         // for buffer in user_buf.buffers.iter() {
         //     print!("{}", core::str::from_utf8(*buffer).unwrap());
         // }
         print!("{}", core::str::from_utf8(user_buf).unwrap());
-        user_buf.len()
+        Ok(user_buf.len())
+    }
+    fn ioctl(&self, request: usize, arg: usize) -> SysResult {
+        tty_ioctl(request, arg)
     }
 }
@@ -0,0 +1,342 @@
+//! The console terminal. Input typed on the debug console goes through a
+//! line discipline configured with a subset of termios: line editing in
+//! canonical mode, echo, and signals to the foreground process group.
+
+use polyhal::debug_console::DebugConsole;
+
+use crate::console::print_bytes;
+use crate::mm::UserPtr;
+use crate::sync::WaitQueue;
+use crate::syscall::{Errno, SysResult};
+use crate::task::{
+    current_fatal_pending, current_process, current_signal_pending, current_task,
+    processes_in_group, send_group_signal, SigInfo, SignalFlags, SIG_IGN,
+};
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lazy_static::*;
+use polyhal::utils::MutexNoIrq;
+
+/// Input flags: translate a carriage return into a newline.
+const ICRNL: u32 = 0o400;
+/// Output flags, which are reported but not applied.
+const OPOST: u32 = 0o1;
+const ONLCR: u32 = 0o4;
+/// Control flags: 38400 baud, 8 bit characters, enable the receiver.
+const B38400: u32 = 0o17;
+const CS8: u32 = 0o60;
+const CREAD: u32 = 0o200;
+/// Local flags.
+const ISIG: u32 = 0o1;
+const ICANON: u32 = 0o2;
+const ECHO: u32 = 0o10;
+const ECHOE: u32 = 0o20;
+const ECHOK: u32 = 0o40;
+const ECHOCTL: u32 = 0o1000;
+
+/// Indices of the special characters.
+const VINTR: usize = 0;
+const VQUIT: usize = 1;
+const VERASE: usize = 2;
+const VKILL: usize = 3;
+const VEOF: usize = 4;
+const VMIN: usize = 6;
+const VSUSP: usize = 10;
+const NCCS: usize = 19;
+
+const BS: u8 = 0x08;
+
+/// Get and set the terminal attributes, after flushing the input for
+/// TCSETSF. Output is never buffered, so TCSETSW doesn't wait.
+const TCGETS: usize = 0x5401;
+const TCSETS: usize = 0x5402;
+const TCSETSW: usize = 0x5403;
+const TCSETSF: usize = 0x5404;
+/// Get and set the foreground process group of the console.
+const TIOCGPGRP: usize = 0x540f;
+const TIOCSPGRP: usize = 0x5410;
+
+/// Terminal attributes, laid out as the `struct termios` of Linux.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Termios {
+    pub iflag: u32,
+    pub oflag: u32,
+    pub cflag: u32,
+    pub lflag: u32,
+    pub line: u8,
+    pub cc: [u8; NCCS],
+}
+
+impl Default for Termios {
+    fn default() -> Self {
+        let mut cc = [0u8; NCCS];
+        cc[VINTR] = 0x03; // ^C
+        cc[VQUIT] = 0x1c; // ^\
+        cc[VERASE] = 0x7f; // DEL
+        cc[VKILL] = 0x15; // ^U
+        cc[VEOF] = 0x04; // ^D
+        cc[VMIN] = 1;
+        cc[VSUSP] = 0x1a; // ^Z
+        Self {
+            iflag: ICRNL,
+            oflag: OPOST | ONLCR,
+            cflag: B38400 | CS8 | CREAD,
+            lflag: ISIG | ICANON | ECHO | ECHOE | ECHOK | ECHOCTL,
+            line: 0,
+            cc,
+        }
+    }
+}
+
+struct Tty {
+    termios: Termios,
+    /// Input ready to be read. In canonical mode, `None` ends a line typed
+    /// with ^D and isn't read, so ^D on an empty line reads as end of file.
+    input: VecDeque<Option<u8>>,
+    /// The line being edited in canonical mode.
+    line: Vec<u8>,
+}
+
+impl Tty {
+    fn new() -> Self {
+        Self {
+            termios: Termios::default(),
+            input: VecDeque::new(),
+            line: Vec::new(),
+        }
+    }
+    fn lflag(&self, flag: u32) -> bool {
+        self.termios.lflag & flag != 0
+    }
+    fn echo(&self, ch: u8, echo: &mut Vec<u8>) {
+        if !self.lflag(ECHO) {
+            return;
+        }
+        if self.lflag(ECHOCTL) && ch < b' ' && ch != b'\n' && ch != b'\t' {
+            echo.push(b'^');
+            echo.push(ch + b'@');
+        } else {
+            echo.push(ch);
+        }
+    }
+    /// Remove the last character of the line being edited.
+    fn erase(&mut self, echo: &mut Vec<u8>) {
+        if self.line.pop().is_some() && self.lflag(ECHO) && self.lflag(ECHOE) {
+            echo.extend_from_slice(&[BS, b' ', BS]);
+        }
+    }
+    /// Process a character typed on the console, return the signal it
+    /// generates for the foreground process group.
+    fn receive(&mut self, mut ch: u8, echo: &mut Vec<u8>) -> Option<SignalFlags> {
+        let cc = self.termios.cc;
+        if ch == b'\r' && self.termios.iflag & ICRNL != 0 {
+            ch = b'\n';
+        }
+        if self.lflag(ISIG) && (ch == cc[VINTR] || ch == cc[VSUSP]) {
+            // what has been typed but not read is discarded
+            self.input.clear();
+            self.line.clear();
+            self.echo(ch, echo);
+            if ch == cc[VINTR] {
+                return Some(SignalFlags::SIGINT);
+            }
+            return Some(SignalFlags::SIGTSTP);
+        }
+        if !self.lflag(ICANON) {
+            self.input.push_back(Some(ch));
+            self.echo(ch, echo);
+            return None;
+        }
+        if ch == cc[VERASE] || ch == BS {
+            self.erase(echo);
+        } else if ch == cc[VKILL] {
+            if self.lflag(ECHOK) {
+                while !self.line.is_empty() {
+                    self.erase(echo);
+                }
+            } else {
+                self.line.clear();
+            }
+        } else if ch == cc[VEOF] {
+            self.input.extend(self.line.drain(..).map(Some));
+            self.input.push_back(None);
+        } else {
+            self.line.push(ch);
+            self.echo(ch, echo);
+            if ch == b'\n' {
+                self.input.extend(self.line.drain(..).map(Some));
+            }
+        }
+        None
+    }
+    /// Read the available input into `buf`, at most one line in canonical
+    /// mode. Return None if the reader has to wait.
+    fn read(&mut self, buf: &mut [u8]) -> Option<usize> {
+        let canonical = self.lflag(ICANON);
+        let mut len = 0;
+        while len < buf.len() {
+            match self.input.pop_front() {
+                Some(Some(ch)) => {
+                    buf[len] = ch;
+                    len += 1;
+                    if canonical && ch == b'\n' {
+                        return Some(len);
+                    }
+                }
+                // the end of a line typed with ^D
+                Some(None) if canonical => return Some(len),
+                Some(None) => {}
+                None if len == 0 && (canonical || self.termios.cc[VMIN] > 0) => return None,
+                None => break,
+            }
+        }
+        // don't take the rest of a line ended with ^D for end of file
+        if canonical && self.input.front() == Some(&None) {
+            self.input.pop_front();
+        }
+        Some(len)
+    }
+    fn set_termios(&mut self, termios: Termios, flush: bool) {
+        if flush {
+            self.input.clear();
+            self.line.clear();
+        }
+        self.termios = termios;
+        if !self.lflag(ICANON) {
+            // the line being edited can be read in raw mode
+            self.input.extend(self.line.drain(..).map(Some));
+        }
+    }
+}
+
+lazy_static! {
+    static ref TTY: MutexNoIrq<Tty> = MutexNoIrq::new(Tty::new());
+    /// Tasks waiting for console input.
+    static ref TTY_QUEUE: WaitQueue = WaitQueue::new();
+}
+
+/// The process group which owns the console, 0 if any process may read it.
+static FOREGROUND_PGID: AtomicUsize = AtomicUsize::new(0);
+
+fn foreground_pgid() -> usize {
+    FOREGROUND_PGID.load(Ordering::SeqCst)
+}
+
+/// A process out of the foreground group stops with SIGTTIN when it reads
+/// the console, and reads again once it is continued. It can't read if it
+/// blocks or ignores SIGTTIN.
+fn check_foreground() -> Result<(), Errno> {
+    let foreground = foreground_pgid();
+    let task = current_task().unwrap();
+    let process_inner = task.process.inner_exclusive_access();
+    let pgid = process_inner.pgid;
+    let ignored =
+        process_inner.signal_actions.table[SignalFlags::SIGTTIN.signum()].handler == SIG_IGN;
+    drop(process_inner);
+    if foreground == 0 || pgid == foreground {
+        return Ok(());
+    }
+    if ignored
+        || task
+            .inner_exclusive_access()
+            .signal_mask
+            .contains(SignalFlags::SIGTTIN)
+    {
+        return Err(Errno::EIO);
+    }
+    send_group_signal(pgid, SigInfo::kernel(SignalFlags::SIGTTIN));
+    Err(Errno::ERESTARTSYS)
+}
+
+/// Pass the pending console input through the line discipline and wake up
+/// the readers. The console doesn't raise interrupts, so this is polled.
+pub fn poll_stdin() {
+    let mut tty = TTY.lock();
+    let mut echo = Vec::new();
+    let mut signals = Vec::new();
+    let mut received = false;
+    while let Some(ch) = DebugConsole::getchar() {
+        if let Some(signal) = tty.receive(ch, &mut echo) {
+            signals.push(signal);
+        }
+        received = true;
+    }
+    drop(tty);
+    print_bytes(&echo);
+    if received {
+        TTY_QUEUE.wake_all();
+    }
+    let foreground = foreground_pgid();
+    if foreground != 0 {
+        for signal in signals {
+            send_group_signal(foreground, SigInfo::kernel(signal));
+        }
+    }
+}
+
+/// Read the console into `buf`, blocking until there is input. A signal
+/// interrupts the wait, the read is made again after the handler only with
+/// SA_RESTART.
+pub fn tty_read(buf: &mut [u8]) -> Result<usize, Errno> {
+    if buf.is_empty() {
+        return Ok(0);
+    }
+    loop {
+        check_foreground()?;
+        poll_stdin();
+        let mut tty = TTY.lock();
+        if let Some(len) = tty.read(buf) {
+            return Ok(len);
+        }
+        if current_fatal_pending() || current_signal_pending() {
+            return Err(Errno::ERESTARTSYS);
+        }
+        TTY_QUEUE.wait_with(tty);
+    }
+}
+
+pub fn tty_ioctl(request: usize, arg: usize) -> SysResult {
+    let process = current_process();
+    match request {
+        TCGETS => {
+            let termios = TTY.lock().termios;
+            let termios_ptr = UserPtr::new(arg as *const Termios);
+            termios_ptr.write(&mut process.inner_exclusive_access().memory_set, termios)?;
+            Ok(0)
+        }
+        TCSETS | TCSETSW | TCSETSF => {
+            let termios_ptr = UserPtr::new(arg as *const Termios);
+            let termios = termios_ptr.read(&mut process.inner_exclusive_access().memory_set)?;
+            TTY.lock().set_termios(termios, request == TCSETSF);
+            Ok(0)
+        }
+        TIOCGPGRP => {
+            let pgid = foreground_pgid() as i32;
+            let pgid_ptr = UserPtr::new(arg as *const i32);
+            pgid_ptr.write(&mut process.inner_exclusive_access().memory_set, pgid)?;
+            Ok(0)
+        }
+        TIOCSPGRP => {
+            let pgid_ptr = UserPtr::new(arg as *const i32);
+            let mut inner = process.inner_exclusive_access();
+            let pgid = pgid_ptr.read(&mut inner.memory_set)?;
+            let sid = inner.sid;
+            drop(inner);
+            if pgid <= 0 {
+                return Err(Errno::EINVAL);
+            }
+            // only a group of the same session can own the console
+            let in_session = processes_in_group(pgid as usize)
+                .iter()
+                .any(|process| process.inner_exclusive_access().sid == sid);
+            if !in_session {
+                return Err(Errno::EPERM);
+            }
+            FOREGROUND_PGID.store(pgid as usize, Ordering::SeqCst);
+            Ok(0)
+        }
+        _ => Err(Errno::ENOTTY),
+    }
+}
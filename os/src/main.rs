@@ -2,11 +2,18 @@
 #![no_main]
 #![feature(alloc_error_handler)]
 
+use core::sync::atomic::{AtomicBool, Ordering};
+
 use crate::{
-    syscall::syscall,
+    config::SIGRETURN_TRAMPOLINE,
+    mm::MapPermission,
+    syscall::{syscall, Errno},
     task::{
-        check_signals_error_of_current, current_add_signal, exit_current_and_run_next,
-        handle_signals, suspend_current_and_run_next, SignalFlags,
+        check_signals_error_of_current, current_add_signal, current_charge_user_time,
+        current_check_cpu_timers, current_handle_page_fault, current_process_exiting,
+        current_segv_info, current_sigreturn, current_slice_expired, exit_current_and_run_next,
+        exit_current_process_and_run_next, handle_signals, leave_user,
+        suspend_current_and_run_next, SigInfo, SignalFlags, ILL_ILLOPC,
     },
 };
 // use polyhal::api::ArchInterface;
@@ -33,6 +40,7 @@ mod mm;
 mod sync;
 mod syscall;
 mod task;
+mod timer;
 
 pub struct ArchInterfaceImpl;
 
@@ -40,20 +48,42 @@ pub struct ArchInterfaceImpl;
 #[polyhal::arch_interrupt]
 fn kernel_interrupt(ctx: &mut TrapFrame, trap_type: TrapType) {
     // trace!("trap_type @ {:x?} {:#x?}", trap_type, ctx);
+    // before any lock, a hart shooting down the TLB may be waiting for us
+    leave_user();
+    current_charge_user_time();
+    // where a syscall interrupted by a signal returns if a handler runs
+    let mut interrupted = None;
     match trap_type {
         Breakpoint => return,
         SysCall => {
+            let syscall_pc = ctx[TrapFrameArgs::SEPC];
             // jump to next instruction anyway
             ctx.syscall_ok();
             let args = ctx.args();
             // get system call return value
             // info!("syscall: {}", ctx[TrapFrameArgs::SYSCALL]);
 
-            let result = syscall(ctx[TrapFrameArgs::SYSCALL], [args[0], args[1], args[2]]);
-            // cx is changed during sys_exec, so we have to call it again
-            ctx[TrapFrameArgs::RET] = result as usize;
+            let result = syscall(ctx[TrapFrameArgs::SYSCALL], args);
+            if result == -(Errno::ERESTARTSYS as isize) {
+                // keep the arguments and make the syscall again, unless a
+                // handler without SA_RESTART runs first
+                interrupted = Some(ctx[TrapFrameArgs::SEPC]);
+                ctx[TrapFrameArgs::SEPC] = syscall_pc;
+            } else {
+                // cx is changed during sys_exec, so we have to call it again
+                ctx[TrapFrameArgs::RET] = result as usize;
+            }
+        }
+        // a signal handler has returned
+        InstructionPageFault(vaddr) if vaddr == SIGRETURN_TRAMPOLINE => {
+            if !current_sigreturn() {
+                current_add_signal(current_segv_info(vaddr, MapPermission::X));
+            }
         }
-        StorePageFault(_paddr) | LoadPageFault(_paddr) | InstructionPageFault(_paddr) => {
+        StorePageFault(vaddr) if current_handle_page_fault(vaddr, MapPermission::W) => {}
+        LoadPageFault(vaddr) if current_handle_page_fault(vaddr, MapPermission::R) => {}
+        InstructionPageFault(vaddr) if current_handle_page_fault(vaddr, MapPermission::X) => {}
+        StorePageFault(vaddr) | LoadPageFault(vaddr) | InstructionPageFault(vaddr) => {
             /*
             println!(
                 "[kernel] {:?} in application, bad addr = {:#x}, bad instruction = {:#x}, kernel killed it.",
@@ -62,13 +92,25 @@ fn kernel_interrupt(ctx: &mut TrapFrame, trap_type: TrapType) {
                 current_trap_cx().sepc,
             );
             */
-            current_add_signal(SignalFlags::SIGSEGV);
+            let perm = match trap_type {
+                StorePageFault(_) => MapPermission::W,
+                LoadPageFault(_) => MapPermission::R,
+                _ => MapPermission::X,
+            };
+            current_add_signal(current_segv_info(vaddr, perm));
         }
         IllegalInstruction(_) => {
-            current_add_signal(SignalFlags::SIGILL);
+            let info = SigInfo::fault(SignalFlags::SIGILL, ILL_ILLOPC, ctx[TrapFrameArgs::SEPC]);
+            current_add_signal(info);
         }
         Timer => {
-            suspend_current_and_run_next();
+            current_check_cpu_timers();
+            timer::check_timer();
+            fs::poll_stdin();
+            // preempt the task once its time slice runs out
+            if current_slice_expired() {
+                suspend_current_and_run_next();
+            }
         }
         _ => {
             warn!("unsuspended trap type: {:?}", trap_type);
@@ -76,20 +118,24 @@ fn kernel_interrupt(ctx: &mut TrapFrame, trap_type: TrapType) {
     }
     // handle signals (handle the sent signal)
     // println!("[K] trap_handler:: handle_signals");
-    handle_signals();
+    handle_signals(interrupted);
 
     // check error signals (if error then exit)
     if let Some((errno, msg)) = check_signals_error_of_current() {
         println!("[kernel] {}", msg);
-        exit_current_and_run_next(errno);
+        exit_current_process_and_run_next(errno);
+    }
+    // another thread has made the process exit
+    if current_process_exiting() {
+        exit_current_and_run_next(0);
     }
 }
 
+/// Set once the boot hart has initialized the kernel.
+static BOOTED: AtomicBool = AtomicBool::new(false);
+
 fn main(hartid: usize) {
     trace!("ch7 main: hartid: {}", hartid);
-    if hartid != 0 {
-        return;
-    }
     println!("[kernel] Hello, world!");
     mm::init_heap();
     logging::init(option_env!("LOG"));
@@ -104,12 +150,24 @@ fn main(hartid: usize) {
 
     fs::list_apps();
     task::init_kernel_page();
+    task::init_hart();
     task::add_initproc();
+    BOOTED.store(true, Ordering::Release);
     task::run_tasks();
     panic!("Unreachable in main function of rCore Tutorial kernel!");
 }
 
-define_entry!(main);
+fn secondary_main(hartid: usize) {
+    while !BOOTED.load(Ordering::Acquire) {
+        core::hint::spin_loop();
+    }
+    println!("[kernel] hart {} is online", hartid);
+    task::init_hart();
+    task::run_tasks();
+    panic!("Unreachable in secondary_main of rCore Tutorial kernel!");
+}
+
+define_entry!(main, secondary_main);
 
 pub struct PageAllocImpl;
 
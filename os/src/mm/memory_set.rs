@@ -1,6 +1,7 @@
 use super::vpn_range::VAddrRange;
-use super::{frame_alloc, FrameTracker};
-use crate::config::{PAGE_SIZE, USER_STACK_SIZE};
+use super::{frame_alloc, BadAddress, FrameTracker};
+use crate::config::{MMAP_BASE, PAGE_SIZE, USER_SPACE_TOP, USER_STACK_SIZE};
+use crate::task::shootdown_tlb;
 use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
@@ -10,6 +11,8 @@ use polyhal::{PhysAddr, VirtAddr};
 pub struct MemorySet {
     page_table: Arc<PageTableWrapper>,
     areas: Vec<MapArea>,
+    heap_bottom: usize,
+    brk: usize,
 }
 
 impl MemorySet {
@@ -17,11 +20,17 @@ impl MemorySet {
         Self {
             page_table: Arc::new(PageTableWrapper::alloc()),
             areas: Vec::new(),
+            heap_bottom: 0,
+            brk: 0,
         }
     }
     pub fn token(&self) -> PageTable {
         self.page_table.0
     }
+    /// Tells the address spaces apart for TLB shootdowns.
+    pub fn space_id(&self) -> usize {
+        space_id(&self.page_table)
+    }
     fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) {
         map_area.map(&self.page_table);
         if let Some(data) = data {
@@ -31,6 +40,7 @@ impl MemorySet {
     }
     /// Include sections in elf and trampoline and TrapContext and user stack,
     /// also returns user_sp and entry point.
+    /// An empty heap is placed above the user stack and grown by `brk`.
     pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
         trace!("os::mm::MemorySet::from_elf");
         let mut memory_set = Self::new_bare();
@@ -57,7 +67,7 @@ impl MemorySet {
                 if ph_flags.is_execute() {
                     map_perm |= MapPermission::X;
                 }
-                let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
+                let map_area = MapArea::new(start_va, end_va, MapType::Lazy, map_perm);
                 max_end_va = map_area.vaddr_range.get_end();
                 memory_set.push(
                     map_area,
@@ -74,11 +84,24 @@ impl MemorySet {
             MapArea::new(
                 user_stack_bottom.into(),
                 user_stack_top.into(),
-                MapType::Framed,
+                MapType::Lazy,
+                MapPermission::R | MapPermission::W | MapPermission::U,
+            ),
+            None,
+        );
+        // map an empty heap after another guard page
+        let heap_bottom = user_stack_top + PAGE_SIZE;
+        memory_set.push(
+            MapArea::new(
+                heap_bottom.into(),
+                heap_bottom.into(),
+                MapType::Lazy,
                 MapPermission::R | MapPermission::W | MapPermission::U,
             ),
             None,
         );
+        memory_set.heap_bottom = heap_bottom;
+        memory_set.brk = heap_bottom;
         // map TrapContext
         (
             memory_set,
@@ -86,29 +109,274 @@ impl MemorySet {
             elf.header.pt2.entry_point() as usize,
         )
     }
+    /// Share every mapped frame with the new space instead of copying it.
+    /// Writable pages are mapped read-only in both spaces and copied on the
+    /// first write, see [`MemorySet::handle_page_fault`].
     pub fn from_existed_user(user_space: &MemorySet) -> MemorySet {
         trace!("os::mm::MemorySet::from_existed_user");
         let mut memory_set = Self::new_bare();
-        // copy data sections/trap_context/user_stack
+        memory_set.heap_bottom = user_space.heap_bottom;
+        memory_set.brk = user_space.brk;
+        let mut protected = false;
         for area in user_space.areas.iter() {
-            let new_area = MapArea::from_another(area);
-            memory_set.push(new_area, None);
-            // copy data from another space
-            for vpn in area.vaddr_range {
-                let src = user_space.translate(vpn).unwrap().0;
-                let dst = memory_set.translate(vpn).unwrap().0;
-                // dst_ppn.get_buffer().copy_from_slice(src_ppn.get_buffer())
-                unsafe {
-                    core::ptr::copy_nonoverlapping(
-                        src.get_ptr::<u8>(),
-                        dst.get_mut_ptr(),
-                        PAGE_SIZE,
+            let mut new_area = MapArea::from_another(area);
+            let cow = area.map_perm.contains(MapPermission::W);
+            for (vaddr, frame) in area.data_frames.iter() {
+                if cow {
+                    protected = true;
+                    // write-protect the page in the parent space as well
+                    user_space.page_table.unmap_page(*vaddr);
+                    user_space.page_table.map_page(
+                        *vaddr,
+                        frame.paddr,
+                        area.cow_flags(),
+                        MappingSize::Page4KB,
                     );
                 }
+                new_area.map_shared(&memory_set.page_table, *vaddr, frame.clone());
             }
+            memory_set.areas.push(new_area);
+        }
+        // the threads of the parent on other harts mustn't write to the
+        // shared frames through their TLB
+        if protected {
+            shootdown_tlb(user_space.space_id());
         }
         memory_set
     }
+    /// Handle a page fault at `vaddr` which requires `perm`. Allocate the
+    /// page if it has not been touched yet, or give the current space a
+    /// private copy if it is shared copy-on-write. The page may be mapped
+    /// already if another thread has faulted on it too.
+    /// Return false if the access is illegal or there is no free frame.
+    pub fn handle_page_fault(&mut self, vaddr: VirtAddr, perm: MapPermission) -> bool {
+        trace!("os::mm::MemorySet::handle_page_fault");
+        let vaddr = vaddr.floor();
+        let page_table = self.page_table.clone();
+        let area = match self
+            .areas
+            .iter_mut()
+            .find(|area| area.vaddr_range.contains(vaddr))
+        {
+            Some(area) => area,
+            None => return false,
+        };
+        if !area.map_perm.contains(perm) {
+            false
+        } else if !area.data_frames.contains_key(&vaddr) {
+            area.map_one(&page_table, vaddr)
+        } else if page_table
+            .translate(vaddr)
+            .is_some_and(|(_, flags)| flags.contains(perm.into()))
+        {
+            // another thread has populated the page or copied it first
+            true
+        } else if perm.contains(MapPermission::W) {
+            area.copy_on_write(&page_table, vaddr)
+        } else {
+            false
+        }
+    }
+    /// Whether `vaddr` is in an area, even if the page is not present.
+    pub fn contains(&self, vaddr: VirtAddr) -> bool {
+        let vaddr = vaddr.floor();
+        self.areas
+            .iter()
+            .any(|area| area.vaddr_range.contains(vaddr))
+    }
+    /// Translate a user address for a kernel access which requires `perm`,
+    /// faulting in the page first if needed.
+    pub fn translate_user(
+        &mut self,
+        vaddr: VirtAddr,
+        perm: MapPermission,
+    ) -> Result<PhysAddr, BadAddress> {
+        let page = vaddr.floor();
+        let offset = usize::from(vaddr) - usize::from(page);
+        let allowed = |flags: MappingFlags| {
+            flags.contains(MappingFlags::U) && flags.contains(MappingFlags::from(perm))
+        };
+        match self.translate(page) {
+            Some((paddr, flags)) if allowed(flags) => return Ok(paddr + offset),
+            _ => {}
+        }
+        if !self.handle_page_fault(page, perm) {
+            return Err(BadAddress);
+        }
+        match self.translate(page) {
+            Some((paddr, flags)) if allowed(flags) => Ok(paddr + offset),
+            _ => Err(BadAddress),
+        }
+    }
+    /// Map an anonymous area of `len` bytes at `start`, or at a free address
+    /// above `MMAP_BASE` if `start` is zero. Return the start of the area, or
+    /// `None` if it overlaps another one or doesn't fit below
+    /// `USER_SPACE_TOP`.
+    pub fn mmap(&mut self, start: usize, len: usize, perm: MapPermission) -> Option<usize> {
+        trace!("os::mm::MemorySet::mmap");
+        let len = len.checked_next_multiple_of(PAGE_SIZE)?;
+        if len > USER_SPACE_TOP - MMAP_BASE {
+            return None;
+        }
+        let start = if start == 0 {
+            self.find_free_area(len)
+        } else {
+            start
+        };
+        let end = start.checked_add(len)?;
+        if end > USER_SPACE_TOP {
+            return None;
+        }
+        let start_va = VirtAddr::new(start);
+        let end_va = VirtAddr::new(end);
+        if self.find_overlap(start_va, end_va).is_some() {
+            return None;
+        }
+        self.push(MapArea::new(start_va, end_va, MapType::Lazy, perm), None);
+        Some(start)
+    }
+    /// Map the user stack of a new thread at a free address above `MMAP_BASE`,
+    /// leaving a guard page below it. Return the top of the stack.
+    pub fn alloc_user_stack(&mut self) -> usize {
+        trace!("os::mm::MemorySet::alloc_user_stack");
+        let bottom = self.find_free_area(USER_STACK_SIZE + PAGE_SIZE) + PAGE_SIZE;
+        let top = bottom + USER_STACK_SIZE;
+        self.push(
+            MapArea::new(
+                bottom.into(),
+                top.into(),
+                MapType::Lazy,
+                MapPermission::R | MapPermission::W | MapPermission::U,
+            ),
+            None,
+        );
+        top
+    }
+    /// Unmap every page in `[start, start + len)`, splitting the areas which
+    /// partially overlap the range.
+    pub fn munmap(&mut self, start: usize, len: usize) {
+        trace!("os::mm::MemorySet::munmap");
+        let start_va = VirtAddr::new(start);
+        let end_va = VirtAddr::new(start + len).ceil();
+        self.split_at(start_va);
+        self.split_at(end_va);
+        let page_table = self.page_table.clone();
+        let mut frames = Vec::new();
+        self.areas.retain_mut(|area| {
+            let inside =
+                start_va <= area.vaddr_range.get_start() && area.vaddr_range.get_end() <= end_va;
+            if inside {
+                frames.extend(area.unmap(&page_table));
+            }
+            !inside
+        });
+        // free the frames once no hart can reach them
+        if !frames.is_empty() {
+            self.shootdown();
+        }
+    }
+    /// Change the permission of `[start, start + len)`.
+    /// Return false if part of the range is not mapped.
+    pub fn mprotect(&mut self, start: usize, len: usize, perm: MapPermission) -> bool {
+        trace!("os::mm::MemorySet::mprotect");
+        let start_va = VirtAddr::new(start);
+        let end_va = VirtAddr::new(start + len).ceil();
+        let mapped = VAddrRange::new(start_va, end_va).into_iter().all(|vaddr| {
+            self.areas
+                .iter()
+                .any(|area| area.vaddr_range.contains(vaddr))
+        });
+        if !mapped {
+            return false;
+        }
+        self.split_at(start_va);
+        self.split_at(end_va);
+        let page_table = self.page_table.clone();
+        let mut remapped = false;
+        self.areas
+            .iter_mut()
+            .filter(|area| {
+                start_va <= area.vaddr_range.get_start() && area.vaddr_range.get_end() <= end_va
+            })
+            .for_each(|area| remapped |= area.change_perm(&page_table, perm));
+        // the permission may have been taken away
+        if remapped {
+            self.shootdown();
+        }
+        true
+    }
+    /// Move the program break to `new_brk`. Return the new break, or the
+    /// current one if `new_brk` is below the heap, reaches `MMAP_BASE` or
+    /// runs into another area.
+    pub fn brk(&mut self, new_brk: usize) -> usize {
+        trace!("os::mm::MemorySet::brk");
+        if new_brk < self.heap_bottom || new_brk > MMAP_BASE {
+            return self.brk;
+        }
+        let Some(new_end) = new_brk.checked_next_multiple_of(PAGE_SIZE) else {
+            return self.brk;
+        };
+        let heap_bottom = VirtAddr::new(self.heap_bottom);
+        let new_end = VirtAddr::new(new_end);
+        let blocked = self.areas.iter().any(|area| {
+            area.vaddr_range.get_start() != heap_bottom
+                && area.vaddr_range.get_start() < new_end
+                && heap_bottom < area.vaddr_range.get_end()
+        });
+        if blocked {
+            return self.brk;
+        }
+        let page_table = self.page_table.clone();
+        match self
+            .areas
+            .iter_mut()
+            .find(|area| area.vaddr_range.get_start() == heap_bottom)
+        {
+            Some(area) => {
+                let frames = area.resize(&page_table, new_end);
+                // free the frames once no hart can reach them
+                if !frames.is_empty() {
+                    self.shootdown();
+                }
+            }
+            // the heap has been unmapped by munmap
+            None => self.push(
+                MapArea::new(
+                    heap_bottom,
+                    new_end,
+                    MapType::Lazy,
+                    MapPermission::R | MapPermission::W | MapPermission::U,
+                ),
+                None,
+            ),
+        }
+        self.brk = new_brk;
+        new_brk
+    }
+    fn find_overlap(&self, start: VirtAddr, end: VirtAddr) -> Option<&MapArea> {
+        self.areas
+            .iter()
+            .find(|area| area.vaddr_range.get_start() < end && start < area.vaddr_range.get_end())
+    }
+    fn find_free_area(&self, len: usize) -> usize {
+        let mut start = VirtAddr::new(MMAP_BASE);
+        while let Some(area) = self.find_overlap(start, start + len) {
+            start = area.vaddr_range.get_end();
+        }
+        start.into()
+    }
+    /// Split the area which contains `vaddr` so that `vaddr` becomes a boundary.
+    fn split_at(&mut self, vaddr: VirtAddr) {
+        if let Some(idx) = self.areas.iter().position(|area| {
+            area.vaddr_range.get_start() < vaddr && vaddr < area.vaddr_range.get_end()
+        }) {
+            let upper = self.areas[idx].split_off(vaddr);
+            self.areas.push(upper);
+        }
+    }
+    fn shootdown(&self) {
+        shootdown_tlb(self.space_id());
+    }
     pub fn activate(&self) {
         self.page_table.change();
     }
@@ -123,9 +391,14 @@ impl MemorySet {
     }
 }
 
+/// The address space which `page_table` belongs to, for TLB shootdowns.
+fn space_id(page_table: &Arc<PageTableWrapper>) -> usize {
+    Arc::as_ptr(page_table) as usize
+}
+
 pub struct MapArea {
     pub vaddr_range: VAddrRange,
-    data_frames: BTreeMap<VirtAddr, FrameTracker>,
+    data_frames: BTreeMap<VirtAddr, Arc<FrameTracker>>,
     map_type: MapType,
     map_perm: MapPermission,
 }
@@ -159,37 +432,162 @@ impl MapArea {
     }
     pub fn map(&mut self, page_table: &Arc<PageTableWrapper>) {
         trace!("os::mm::memory_set::MapArea::map");
+        if self.map_type == MapType::Lazy {
+            return;
+        }
         for vaddr in self.vaddr_range {
-            // self.map_one(page_table, vpn);
-            let p_tracker = frame_alloc().expect("can't allocate frame");
-            page_table.map_page(
-                vaddr,
-                p_tracker.paddr,
-                self.map_perm.into(),
-                MappingSize::Page4KB,
-            );
-            self.data_frames.insert(vaddr, p_tracker);
+            if !self.map_one(page_table, vaddr) {
+                panic!("can't allocate frame");
+            }
+        }
+    }
+
+    /// Allocate a frame for the page at `vaddr` and map it. Return false if
+    /// there is no free frame.
+    pub fn map_one(&mut self, page_table: &Arc<PageTableWrapper>, vaddr: VirtAddr) -> bool {
+        let Some(p_tracker) = frame_alloc() else {
+            return false;
+        };
+        page_table.map_page(
+            vaddr,
+            p_tracker.paddr,
+            self.map_perm.into(),
+            MappingSize::Page4KB,
+        );
+        self.data_frames.insert(vaddr, Arc::new(p_tracker));
+        true
+    }
+
+    /// Keep `[start, vaddr)` in this area and return `[vaddr, end)`.
+    pub fn split_off(&mut self, vaddr: VirtAddr) -> MapArea {
+        let end = self.vaddr_range.get_end();
+        self.vaddr_range = VAddrRange::new(self.vaddr_range.get_start(), vaddr);
+        Self {
+            vaddr_range: VAddrRange::new(vaddr, end),
+            data_frames: self.data_frames.split_off(&vaddr),
+            map_type: self.map_type,
+            map_perm: self.map_perm,
+        }
+    }
+
+    /// Move the end of the area to `new_end`, unmapping the pages above it.
+    /// Return their frames, which are freed once the TLBs are shot down.
+    pub fn resize(
+        &mut self,
+        page_table: &Arc<PageTableWrapper>,
+        new_end: VirtAddr,
+    ) -> Vec<Arc<FrameTracker>> {
+        let mut frames = Vec::new();
+        if new_end < self.vaddr_range.get_end() {
+            frames = self.split_off(new_end).unmap(page_table);
+        }
+        self.vaddr_range = VAddrRange::new(self.vaddr_range.get_start(), new_end);
+        frames
+    }
+
+    /// Remap the populated pages with `perm`. Return whether there is any.
+    pub fn change_perm(&mut self, page_table: &Arc<PageTableWrapper>, perm: MapPermission) -> bool {
+        self.map_perm = perm;
+        for (vaddr, frame) in self.data_frames.iter() {
+            // frames still shared with another space stay write-protected
+            let flags = if Arc::strong_count(frame) > 1 {
+                self.cow_flags()
+            } else {
+                perm.into()
+            };
+            page_table.unmap_page(*vaddr);
+            page_table.map_page(*vaddr, frame.paddr, flags, MappingSize::Page4KB);
         }
+        !self.data_frames.is_empty()
     }
 
-    /// Unmap page area
-    #[allow(unused)]
-    pub fn unmap(&mut self, page_table: &Arc<PageTableWrapper>) {
+    /// Map a frame owned by another space. Writable pages are mapped
+    /// read-only so that the first write triggers copy-on-write.
+    pub fn map_shared(
+        &mut self,
+        page_table: &Arc<PageTableWrapper>,
+        vaddr: VirtAddr,
+        frame: Arc<FrameTracker>,
+    ) {
+        page_table.map_page(vaddr, frame.paddr, self.cow_flags(), MappingSize::Page4KB);
+        self.data_frames.insert(vaddr, frame);
+    }
+
+    /// Flags of a page which is shared between spaces.
+    fn cow_flags(&self) -> MappingFlags {
+        (self.map_perm - MapPermission::W).into()
+    }
+
+    /// Copy the shared frame at `vaddr` if it is still referenced by another
+    /// space, then map the page writable again. Return false if the page
+    /// can't be written or there is no free frame for the copy.
+    pub fn copy_on_write(&mut self, page_table: &Arc<PageTableWrapper>, vaddr: VirtAddr) -> bool {
+        if !self.map_perm.contains(MapPermission::W) {
+            return false;
+        }
+        let frame = match self.data_frames.get_mut(&vaddr) {
+            Some(frame) => frame,
+            None => return false,
+        };
+        match page_table.translate(vaddr) {
+            Some((_, flags)) if !flags.contains(MappingFlags::W) => {}
+            _ => return false,
+        }
+        let mut copied = false;
+        if Arc::strong_count(frame) > 1 {
+            let Some(new_frame) = frame_alloc() else {
+                return false;
+            };
+            unsafe {
+                core::ptr::copy_nonoverlapping(
+                    frame.paddr.get_ptr::<u8>(),
+                    new_frame.paddr.get_mut_ptr(),
+                    PAGE_SIZE,
+                );
+            }
+            *frame = Arc::new(new_frame);
+            copied = true;
+        }
+        page_table.unmap_page(vaddr);
+        page_table.map_page(
+            vaddr,
+            frame.paddr,
+            self.map_perm.into(),
+            MappingSize::Page4KB,
+        );
+        // the other threads mustn't read the old frame any more
+        if copied {
+            shootdown_tlb(space_id(page_table));
+        }
+        true
+    }
+
+    /// Unmap page area. Return the frames, which are freed once the TLBs
+    /// are shot down.
+    pub fn unmap(&mut self, page_table: &Arc<PageTableWrapper>) -> Vec<Arc<FrameTracker>> {
         trace!("os::mm::memory_set::MapArea::unmap");
-        for vpn in self.vaddr_range {
-            page_table.unmap_page(vpn);
+        // pages of a lazy area may never have been mapped
+        for vaddr in self.data_frames.keys() {
+            page_table.unmap_page(*vaddr);
         }
+        core::mem::take(&mut self.data_frames)
+            .into_values()
+            .collect()
     }
 
     /// data: start-aligned but maybe with shorter length
     /// assume that all frames were cleared before
+    /// pages of a lazy area which hold data are populated here
     pub fn copy_data(&mut self, page_table: &Arc<PageTableWrapper>, data: &[u8]) {
         trace!("os::mm::memory_set::MapArea::copy_data");
-        assert_eq!(self.map_type, MapType::Framed);
         let mut start: usize = 0;
         let mut curr_vaddr = self.vaddr_range.get_start();
         let len = data.len();
         loop {
+            if !self.data_frames.contains_key(&curr_vaddr) && !self.map_one(page_table, curr_vaddr)
+            {
+                panic!("can't allocate frame");
+            }
             let src = &data[start..len.min(start + PAGE_SIZE)];
             let dst = &mut page_table
                 .translate(curr_vaddr.into())
@@ -211,6 +609,8 @@ impl MapArea {
 pub enum MapType {
     //  Identical, not used now
     Framed,
+    /// frames are allocated on the first page fault
+    Lazy,
 }
 
 bitflags! {
@@ -222,19 +622,19 @@ bitflags! {
     }
 }
 
-impl Into<MappingFlags> for MapPermission {
-    fn into(self) -> MappingFlags {
+impl From<MapPermission> for MappingFlags {
+    fn from(perm: MapPermission) -> Self {
         let mut flags = MappingFlags::empty();
-        if self.contains(MapPermission::R) {
+        if perm.contains(MapPermission::R) {
             flags |= MappingFlags::R;
         }
-        if self.contains(MapPermission::W) {
+        if perm.contains(MapPermission::W) {
             flags |= MappingFlags::W;
         }
-        if self.contains(MapPermission::X) {
+        if perm.contains(MapPermission::X) {
             flags |= MappingFlags::X;
         }
-        if self.contains(MapPermission::U) {
+        if perm.contains(MapPermission::U) {
             flags |= MappingFlags::U;
         }
         flags
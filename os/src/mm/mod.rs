@@ -2,6 +2,7 @@ mod frame_allocator;
 mod heap_allocator;
 mod memory_set;
 mod page_table;
+mod user_ptr;
 mod vpn_range;
 
 pub use frame_allocator::add_frames_range;
@@ -9,5 +10,5 @@ pub use frame_allocator::{
     frame_alloc, frame_alloc_persist, frame_dealloc, frames_alloc, FrameTracker,
 };
 pub use heap_allocator::init_heap;
-pub use memory_set::MemorySet;
-pub use page_table::{translated_byte_buffer, translated_ref, translated_refmut, translated_str};
+pub use memory_set::{MapPermission, MemorySet};
+pub use user_ptr::{read_user_str, BadAddress, UserPtr, UserSlice};
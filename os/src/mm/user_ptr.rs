@@ -0,0 +1,140 @@
+use super::{MapPermission, MemorySet};
+use crate::config::PAGE_SIZE;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::mem::{size_of, MaybeUninit};
+use polyhal::VirtAddr;
+
+/// The user passed an address which is not mapped or lacks the permission.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BadAddress;
+
+/// A buffer of `len` bytes in the user address space.
+#[derive(Debug, Clone, Copy)]
+pub struct UserSlice {
+    ptr: usize,
+    len: usize,
+}
+
+impl UserSlice {
+    pub fn new<T>(ptr: *const T, len: usize) -> Self {
+        Self {
+            ptr: ptr as usize,
+            len,
+        }
+    }
+
+    /// Walk the buffer page by page, calling `f(paddr, offset, len)` for
+    /// every piece which lies in a single page.
+    fn for_each_page(
+        &self,
+        memory_set: &mut MemorySet,
+        perm: MapPermission,
+        mut f: impl FnMut(usize, usize, usize),
+    ) -> Result<(), BadAddress> {
+        self.ptr.checked_add(self.len).ok_or(BadAddress)?;
+        let mut offset = 0;
+        while offset < self.len {
+            let vaddr = self.ptr + offset;
+            let len = (PAGE_SIZE - vaddr % PAGE_SIZE).min(self.len - offset);
+            let paddr = memory_set.translate_user(VirtAddr::new(vaddr), perm)?;
+            f(paddr.get_mut_ptr::<u8>() as usize, offset, len);
+            offset += len;
+        }
+        Ok(())
+    }
+
+    /// Check that the whole buffer is accessible with `perm`.
+    pub fn check(&self, memory_set: &mut MemorySet, perm: MapPermission) -> Result<(), BadAddress> {
+        self.for_each_page(memory_set, perm, |_, _, _| {})
+    }
+
+    /// Copy the user buffer into `dst`, which must be as long as the buffer.
+    pub fn copy_to_kernel(
+        &self,
+        memory_set: &mut MemorySet,
+        dst: &mut [u8],
+    ) -> Result<(), BadAddress> {
+        assert_eq!(dst.len(), self.len);
+        self.for_each_page(memory_set, MapPermission::R, |src, offset, len| unsafe {
+            core::ptr::copy_nonoverlapping(src as *const u8, dst[offset..].as_mut_ptr(), len);
+        })
+    }
+
+    /// Copy `src`, which must be as long as the buffer, into the user buffer.
+    pub fn copy_from_kernel(
+        &self,
+        memory_set: &mut MemorySet,
+        src: &[u8],
+    ) -> Result<(), BadAddress> {
+        assert_eq!(src.len(), self.len);
+        self.for_each_page(memory_set, MapPermission::W, |dst, offset, len| unsafe {
+            core::ptr::copy_nonoverlapping(src[offset..].as_ptr(), dst as *mut u8, len);
+        })
+    }
+}
+
+/// A pointer to a `T` in the user address space.
+pub struct UserPtr<T> {
+    ptr: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Copy> UserPtr<T> {
+    pub fn new(ptr: *const T) -> Self {
+        Self {
+            ptr: ptr as usize,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.ptr == 0
+    }
+
+    /// Pointer to the `index`-th element of an array starting here.
+    pub fn add(&self, index: usize) -> Self {
+        Self {
+            ptr: self.ptr.wrapping_add(index * size_of::<T>()),
+            _marker: PhantomData,
+        }
+    }
+
+    fn as_slice(&self) -> UserSlice {
+        UserSlice {
+            ptr: self.ptr,
+            len: size_of::<T>(),
+        }
+    }
+
+    pub fn read(&self, memory_set: &mut MemorySet) -> Result<T, BadAddress> {
+        let mut value = MaybeUninit::<T>::uninit();
+        let dst = unsafe {
+            core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>())
+        };
+        self.as_slice().copy_to_kernel(memory_set, dst)?;
+        Ok(unsafe { value.assume_init() })
+    }
+
+    pub fn write(&self, memory_set: &mut MemorySet, value: T) -> Result<(), BadAddress> {
+        let src =
+            unsafe { core::slice::from_raw_parts(&value as *const T as *const u8, size_of::<T>()) };
+        self.as_slice().copy_from_kernel(memory_set, src)
+    }
+}
+
+/// Load a string from the user address space without an end `\0`.
+pub fn read_user_str(memory_set: &mut MemorySet, ptr: *const u8) -> Result<String, BadAddress> {
+    let mut bytes = Vec::new();
+    let mut ptr = UserPtr::new(ptr);
+    loop {
+        let byte = ptr.read(memory_set)?;
+        if byte == 0 {
+            break;
+        }
+        bytes.push(byte);
+        ptr = ptr.add(1);
+    }
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
@@ -16,6 +16,9 @@ impl VAddrRange {
     pub fn get_end(&self) -> VirtAddr {
         self.r
     }
+    pub fn contains(&self, vaddr: VirtAddr) -> bool {
+        self.l <= vaddr && vaddr < self.r
+    }
 }
 impl IntoIterator for VAddrRange {
     type Item = VirtAddr;
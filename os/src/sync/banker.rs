@@ -0,0 +1,89 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Allocation of one kind of resources among the threads of a process, for
+/// deadlock detection with the banker's algorithm.
+#[derive(Default)]
+pub struct Banker {
+    /// Free units of each resource.
+    available: Vec<usize>,
+    /// Units of each resource held by each thread, indexed by tid.
+    allocation: Vec<Vec<usize>>,
+    /// Units of each resource each thread is waiting for, indexed by tid.
+    need: Vec<Vec<usize>>,
+}
+
+impl Banker {
+    /// Track resource `id`, which has `count` free units.
+    pub fn add_resource(&mut self, id: usize, count: usize) {
+        if id >= self.available.len() {
+            self.available.resize(id + 1, 0);
+        }
+        self.available[id] = count;
+        for row in self.allocation.iter_mut().chain(self.need.iter_mut()) {
+            if id < row.len() {
+                row[id] = 0;
+            }
+        }
+    }
+    /// Make sure there are rows for thread `tid` and all the resources.
+    fn reserve(&mut self, tid: usize) {
+        let resources = self.available.len();
+        if tid >= self.allocation.len() {
+            self.allocation.resize(tid + 1, Vec::new());
+            self.need.resize(tid + 1, Vec::new());
+        }
+        for row in self.allocation.iter_mut().chain(self.need.iter_mut()) {
+            row.resize(resources, 0);
+        }
+    }
+    /// Thread `tid` starts waiting for one unit of resource `id`.
+    pub fn request(&mut self, tid: usize, id: usize) {
+        self.reserve(tid);
+        self.need[tid][id] += 1;
+    }
+    /// Thread `tid` gives up waiting for resource `id`.
+    pub fn cancel(&mut self, tid: usize, id: usize) {
+        self.reserve(tid);
+        self.need[tid][id] = self.need[tid][id].saturating_sub(1);
+    }
+    /// Thread `tid` gets one unit of resource `id`.
+    pub fn acquire(&mut self, tid: usize, id: usize) {
+        self.reserve(tid);
+        self.need[tid][id] = self.need[tid][id].saturating_sub(1);
+        self.allocation[tid][id] += 1;
+        self.available[id] = self.available[id].saturating_sub(1);
+    }
+    /// Thread `tid` gives back one unit of resource `id`. A semaphore can be
+    /// raised by a thread which doesn't hold it.
+    pub fn release(&mut self, tid: usize, id: usize) {
+        self.reserve(tid);
+        self.allocation[tid][id] = self.allocation[tid][id].saturating_sub(1);
+        self.available[id] += 1;
+    }
+    /// Whether every thread can get what it waits for in some order, given
+    /// that a thread gives back all it holds once it gets its needs. If
+    /// not, the threads may deadlock.
+    pub fn is_safe(&self) -> bool {
+        let mut work = self.available.clone();
+        let mut finish = vec![false; self.need.len()];
+        loop {
+            let next = (0..self.need.len()).find(|&tid| {
+                !finish[tid]
+                    && self.need[tid]
+                        .iter()
+                        .zip(&work)
+                        .all(|(need, work)| need <= work)
+            });
+            match next {
+                Some(tid) => {
+                    for (work, allocation) in work.iter_mut().zip(&self.allocation[tid]) {
+                        *work += allocation;
+                    }
+                    finish[tid] = true;
+                }
+                None => return finish.iter().all(|finish| *finish),
+            }
+        }
+    }
+}
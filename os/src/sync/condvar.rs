@@ -0,0 +1,35 @@
+use super::{Mutex, WaitQueue};
+
+/// A condition variable for user threads.
+pub struct Condvar {
+    wait_queue: WaitQueue,
+}
+
+/// Unlock the mutex once the waiter is in the queue, so a signal sent after
+/// that wakes it up.
+struct Unlocker<'a>(&'a Mutex);
+
+impl Drop for Unlocker<'_> {
+    fn drop(&mut self) {
+        self.0.unlock();
+    }
+}
+
+impl Condvar {
+    pub fn new() -> Self {
+        Self {
+            wait_queue: WaitQueue::new(),
+        }
+    }
+    pub fn signal(&self) {
+        self.wait_queue.wake_one();
+    }
+    /// Unlock `mutex`, which the caller holds, and block until signaled,
+    /// then lock it again. The thread may also be woken up for other
+    /// reasons, so check the condition in a loop. Return false without the
+    /// mutex if the thread has to exit instead.
+    pub fn wait(&self, mutex: &Mutex) -> bool {
+        self.wait_queue.wait_with(Unlocker(mutex));
+        mutex.lock()
+    }
+}
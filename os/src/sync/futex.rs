@@ -0,0 +1,79 @@
+use super::WaitQueue;
+use crate::mm::{BadAddress, MapPermission};
+use crate::task::{current_process, current_task};
+use crate::timer::{add_timer, remove_timer};
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU32, Ordering};
+use lazy_static::*;
+use polyhal::utils::MutexNoIrq;
+use polyhal::{PhysAddr, VirtAddr};
+
+lazy_static! {
+    /// Wait queues of the futexes which have waiters, keyed by the physical
+    /// address of the futex word.
+    static ref FUTEXES: MutexNoIrq<BTreeMap<usize, Arc<WaitQueue>>> =
+        MutexNoIrq::new(BTreeMap::new());
+}
+
+/// Physical address of the futex word at `uaddr` in the current process.
+///
+/// The page is made writable first, so that a copy-on-write page does not
+/// move to another frame after a task has waited on it.
+fn futex_key(uaddr: usize) -> Result<PhysAddr, BadAddress> {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    inner
+        .memory_set
+        .translate_user(VirtAddr::new(uaddr), MapPermission::W)
+}
+
+/// Block the current task if the futex word at `uaddr` still holds `val`,
+/// until it is woken up or the time reaches `expire_us`.
+///
+/// Return false at once if the word holds another value. A task can also be
+/// woken up by a signal, so the caller checks why it returns.
+pub fn futex_wait(uaddr: usize, val: u32, expire_us: Option<usize>) -> Result<bool, BadAddress> {
+    // hold the table while checking the word, a waker has to wait until we
+    // are in the queue
+    let mut futexes = FUTEXES.lock();
+    let paddr = futex_key(uaddr)?;
+    let word = unsafe { &*paddr.get_ptr::<AtomicU32>() };
+    if word.load(Ordering::SeqCst) != val {
+        return Ok(false);
+    }
+    let queue = futexes
+        .entry(paddr.raw())
+        .or_insert_with(|| Arc::new(WaitQueue::new()))
+        .clone();
+    let task = current_task().unwrap();
+    if let Some(expire_us) = expire_us {
+        add_timer(expire_us, task.clone());
+    }
+    queue.wait_with(futexes);
+    if expire_us.is_some() {
+        remove_timer(&task);
+    }
+    // drop the queue once nobody waits on it
+    let mut futexes = FUTEXES.lock();
+    if queue.is_empty() {
+        futexes.remove(&paddr.raw());
+    }
+    Ok(true)
+}
+
+/// Wake up at most `count` tasks waiting on the futex word at `uaddr`.
+/// Return how many tasks are woken up.
+pub fn futex_wake(uaddr: usize, count: usize) -> Result<usize, BadAddress> {
+    let futexes = FUTEXES.lock();
+    let paddr = futex_key(uaddr)?;
+    let queue = match futexes.get(&paddr.raw()) {
+        Some(queue) => queue,
+        None => return Ok(0),
+    };
+    let mut woken = 0;
+    while woken < count && queue.wake_one() {
+        woken += 1;
+    }
+    Ok(woken)
+}
@@ -1,3 +1,13 @@
-mod up;
+mod banker;
+mod condvar;
+mod futex;
+mod mutex;
+mod semaphore;
+mod wait_queue;
 
-pub use up::UPSafeCell;
+pub use banker::Banker;
+pub use condvar::Condvar;
+pub use futex::{futex_wait, futex_wake};
+pub use mutex::Mutex;
+pub use semaphore::Semaphore;
+pub use wait_queue::WaitQueue;
@@ -0,0 +1,45 @@
+use super::WaitQueue;
+use crate::task::current_fatal_pending;
+use polyhal::utils::MutexNoIrq;
+
+/// A mutex for user threads, which blocks the tasks waiting for it.
+pub struct Mutex {
+    locked: MutexNoIrq<bool>,
+    wait_queue: WaitQueue,
+}
+
+impl Mutex {
+    pub fn new() -> Self {
+        Self {
+            locked: MutexNoIrq::new(false),
+            wait_queue: WaitQueue::new(),
+        }
+    }
+    /// Block until the mutex is ours. Return false without it if the
+    /// thread has to exit instead.
+    pub fn lock(&self) -> bool {
+        loop {
+            let mut locked = self.locked.lock();
+            if !*locked {
+                *locked = true;
+                return true;
+            }
+            if current_fatal_pending() {
+                return false;
+            }
+            self.wait_queue.wait_with(locked);
+        }
+    }
+    /// Return false if the mutex is not locked.
+    pub fn unlock(&self) -> bool {
+        let mut locked = self.locked.lock();
+        if !*locked {
+            return false;
+        }
+        *locked = false;
+        drop(locked);
+        // the woken task competes for the mutex again
+        self.wait_queue.wake_one();
+        true
+    }
+}
@@ -0,0 +1,37 @@
+use super::WaitQueue;
+use crate::task::current_fatal_pending;
+use polyhal::utils::MutexNoIrq;
+
+/// A counting semaphore for user threads.
+pub struct Semaphore {
+    count: MutexNoIrq<usize>,
+    wait_queue: WaitQueue,
+}
+
+impl Semaphore {
+    pub fn new(count: usize) -> Self {
+        Self {
+            count: MutexNoIrq::new(count),
+            wait_queue: WaitQueue::new(),
+        }
+    }
+    pub fn up(&self) {
+        *self.count.lock() += 1;
+        self.wait_queue.wake_one();
+    }
+    /// Block until the count is positive and take one. Return false without
+    /// it if the thread has to exit instead.
+    pub fn down(&self) -> bool {
+        loop {
+            let mut count = self.count.lock();
+            if *count > 0 {
+                *count -= 1;
+                return true;
+            }
+            if current_fatal_pending() {
+                return false;
+            }
+            self.wait_queue.wait_with(count);
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use crate::task::{block_current_and_run_next, current_task, wakeup_task, TaskControlBlock};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use polyhal::utils::MutexNoIrq;
+
+/// A queue of tasks blocked until some condition holds.
+///
+/// A task can also be woken up by other sources such as signals, so callers
+/// should check their condition again in a loop after `wait` returns.
+pub struct WaitQueue {
+    inner: MutexNoIrq<VecDeque<Arc<TaskControlBlock>>>,
+}
+
+impl WaitQueue {
+    pub fn new() -> Self {
+        Self {
+            inner: MutexNoIrq::new(VecDeque::new()),
+        }
+    }
+    /// Block the current task until it is woken up.
+    pub fn wait(&self) {
+        self.wait_with(());
+    }
+    /// Block the current task until it is woken up, releasing `guard` once
+    /// the task is in the queue.
+    ///
+    /// Pass the lock which protects the condition being waited for, then a
+    /// wakeup sent after the condition is checked can't be missed.
+    pub fn wait_with<G>(&self, guard: G) {
+        let task = current_task().unwrap();
+        self.inner.lock().push_back(task.clone());
+        drop(guard);
+        block_current_and_run_next();
+        // we may be woken up by someone else, leave the queue anyway
+        self.inner
+            .lock()
+            .retain(|waiter| !Arc::ptr_eq(waiter, &task));
+    }
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock().is_empty()
+    }
+    /// Wake up the task which waits longest, return false if there is none.
+    pub fn wake_one(&self) -> bool {
+        let waiter = self.inner.lock().pop_front();
+        match waiter {
+            Some(waiter) => {
+                wakeup_task(waiter);
+                true
+            }
+            None => false,
+        }
+    }
+    /// Wake up all the waiting tasks.
+    pub fn wake_all(&self) {
+        let waiters = core::mem::take(&mut *self.inner.lock());
+        for waiter in waiters {
+            wakeup_task(waiter);
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use crate::mm::BadAddress;
+
+/// Linux error numbers, returned negated from syscalls.
+#[allow(unused, clippy::upper_case_acronyms)]
+#[repr(isize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Errno {
+    EPERM = 1,
+    ENOENT = 2,
+    ESRCH = 3,
+    EINTR = 4,
+    EIO = 5,
+    E2BIG = 7,
+    ENOEXEC = 8,
+    EBADF = 9,
+    ECHILD = 10,
+    EAGAIN = 11,
+    ENOMEM = 12,
+    EFAULT = 14,
+    EBUSY = 16,
+    EEXIST = 17,
+    EINVAL = 22,
+    EMFILE = 24,
+    ENOTTY = 25,
+    ESPIPE = 29,
+    EPIPE = 32,
+    EDEADLK = 35,
+    ENOSYS = 38,
+    ETIMEDOUT = 110,
+    /// Never seen by the user: the syscall is run again once the signal
+    /// which interrupts it has been handled, or fails with EINTR if a
+    /// handler without SA_RESTART has run.
+    ERESTARTSYS = 512,
+}
+
+pub type SysResult = Result<isize, Errno>;
+
+impl From<BadAddress> for Errno {
+    fn from(_: BadAddress) -> Self {
+        Errno::EFAULT
+    }
+}
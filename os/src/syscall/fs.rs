@@ -1,99 +1,161 @@
+use super::{Errno, SysResult};
+use crate::config::PAGE_SIZE;
 use crate::fs::{make_pipe, open_file, OpenFlags};
-use crate::mm::{translated_byte_buffer, translated_refmut, translated_str};
-use crate::task::{current_task, current_user_token};
-use alloc::sync::Arc;
+use crate::mm::{read_user_str, MapPermission, UserPtr, UserSlice};
+use crate::task::current_process;
+use alloc::vec;
 
-pub fn sys_write(fd: usize, buf: *mut u8, len: usize) -> isize {
-    let token = current_user_token();
-    let task = current_task().unwrap();
-    let inner = task.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
-    }
-    if let Some(file) = &inner.fd_table[fd] {
-        if !file.writable() {
-            return -1;
-        }
-        let file = file.clone();
-        // release current task TCB manually to avoid multi-borrow
-        drop(inner);
-        file.write(translated_byte_buffer(token, buf, len)) as isize
+/// The address and length of the user buffer at `buf + done` up to the end
+/// of its page, or of the buffer. Files are read and written through a
+/// bounce buffer of one page, so the kernel heap doesn't grow with the
+/// length the user passes.
+fn next_chunk(buf: *const u8, done: usize, len: usize) -> (usize, usize) {
+    let addr = buf as usize + done;
+    (addr, (PAGE_SIZE - addr % PAGE_SIZE).min(len - done))
+}
+
+/// The result of a transfer which has failed with `err` after `done` bytes.
+fn partial(done: usize, err: Errno) -> SysResult {
+    if done > 0 {
+        Ok(done as isize)
     } else {
-        -1
+        Err(err)
     }
 }
 
-pub fn sys_read(fd: usize, buf: *mut u8, len: usize) -> isize {
-    let token = current_user_token();
-    let task = current_task().unwrap();
-    let inner = task.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
+pub fn sys_write(fd: usize, buf: *mut u8, len: usize) -> SysResult {
+    let process = current_process();
+    let file = process
+        .inner_exclusive_access()
+        .get_file(fd)
+        .ok_or(Errno::EBADF)?;
+    if !file.writable() {
+        return Err(Errno::EBADF);
     }
-    if let Some(file) = &inner.fd_table[fd] {
-        let file = file.clone();
-        if !file.readable() {
-            return -1;
+    (buf as usize).checked_add(len).ok_or(Errno::EFAULT)?;
+    let mut buffer = vec![0u8; PAGE_SIZE];
+    let mut done = 0;
+    while done < len {
+        let (addr, chunk_len) = next_chunk(buf, done, len);
+        let chunk = &mut buffer[..chunk_len];
+        let copied = UserSlice::new(addr as *const u8, chunk_len)
+            .copy_to_kernel(&mut process.inner_exclusive_access().memory_set, chunk);
+        if let Err(err) = copied {
+            return partial(done, err.into());
+        }
+        let written = match file.write(chunk) {
+            Ok(written) => written,
+            Err(err) => return partial(done, err),
+        };
+        done += written;
+        // stop at the first short write
+        if written < chunk.len() {
+            break;
         }
-        // release current task TCB manually to avoid multi-borrow
-        drop(inner);
-        file.read(translated_byte_buffer(token, buf, len)) as isize
-    } else {
-        -1
     }
+    Ok(done as isize)
 }
 
-pub fn sys_open(path: *const u8, flags: u32) -> isize {
-    let task = current_task().unwrap();
-    let token = current_user_token();
-    let path = translated_str(token, path);
-    if let Some(inode) = open_file(path.as_str(), OpenFlags::from_bits(flags).unwrap()) {
-        let mut inner = task.inner_exclusive_access();
-        let fd = inner.alloc_fd();
-        inner.fd_table[fd] = Some(inode);
-        fd as isize
-    } else {
-        -1
+pub fn sys_read(fd: usize, buf: *mut u8, len: usize) -> SysResult {
+    let process = current_process();
+    let file = process
+        .inner_exclusive_access()
+        .get_file(fd)
+        .ok_or(Errno::EBADF)?;
+    if !file.readable() {
+        return Err(Errno::EBADF);
     }
+    (buf as usize).checked_add(len).ok_or(Errno::EFAULT)?;
+    let mut buffer = vec![0u8; PAGE_SIZE];
+    let mut done = 0;
+    while done < len {
+        let (addr, chunk_len) = next_chunk(buf, done, len);
+        let chunk = &mut buffer[..chunk_len];
+        // check before reading, the data read can't be put back
+        let checked = UserSlice::new(addr as *const u8, chunk_len).check(
+            &mut process.inner_exclusive_access().memory_set,
+            MapPermission::W,
+        );
+        if let Err(err) = checked {
+            return partial(done, err.into());
+        }
+        let read_len = match file.read(chunk) {
+            Ok(read_len) => read_len,
+            Err(err) => return partial(done, err),
+        };
+        let copied = UserSlice::new(addr as *const u8, read_len).copy_from_kernel(
+            &mut process.inner_exclusive_access().memory_set,
+            &chunk[..read_len],
+        );
+        if let Err(err) = copied {
+            return partial(done, err.into());
+        }
+        done += read_len;
+        // stop at the first short read, e.g. a line from the console
+        if read_len < chunk.len() {
+            break;
+        }
+    }
+    Ok(done as isize)
 }
 
-pub fn sys_close(fd: usize) -> isize {
-    let task = current_task().unwrap();
-    let mut inner = task.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
-    }
-    if inner.fd_table[fd].is_none() {
-        return -1;
-    }
-    inner.fd_table[fd].take();
-    0
+/// Device specific request `request` on file `fd`.
+pub fn sys_ioctl(fd: usize, request: usize, arg: usize) -> SysResult {
+    let file = current_process()
+        .inner_exclusive_access()
+        .get_file(fd)
+        .ok_or(Errno::EBADF)?;
+    file.ioctl(request, arg)
 }
 
-pub fn sys_pipe(pipe: *mut usize) -> isize {
-    let task = current_task().unwrap();
-    let token = current_user_token();
-    let mut inner = task.inner_exclusive_access();
+pub fn sys_open(path: *const u8, flags: u32) -> SysResult {
+    let process = current_process();
+    let path = read_user_str(&mut process.inner_exclusive_access().memory_set, path)?;
+    let flags = OpenFlags::from_bits(flags).ok_or(Errno::EINVAL)?;
+    let inode = open_file(path.as_str(), flags).ok_or(Errno::ENOENT)?;
+    let mut inner = process.inner_exclusive_access();
+    let fd = inner.alloc_fd();
+    inner.fd_table[fd] = Some(inode);
+    Ok(fd as isize)
+}
+
+pub fn sys_close(fd: usize) -> SysResult {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    inner.get_file(fd).ok_or(Errno::EBADF)?;
+    let file = inner.fd_table[fd].take();
+    // closing a pipe end wakes tasks up, so not under the PCB
+    drop(inner);
+    drop(file);
+    Ok(0)
+}
+
+pub fn sys_pipe(pipe: *mut usize) -> SysResult {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
     let (pipe_read, pipe_write) = make_pipe();
     let read_fd = inner.alloc_fd();
     inner.fd_table[read_fd] = Some(pipe_read);
     let write_fd = inner.alloc_fd();
     inner.fd_table[write_fd] = Some(pipe_write);
-    *translated_refmut(token, pipe) = read_fd;
-    *translated_refmut(token, unsafe { pipe.add(1) }) = write_fd;
-    0
+    let pipe = UserPtr::new(pipe as *const [usize; 2]);
+    if let Err(err) = pipe.write(&mut inner.memory_set, [read_fd, write_fd]) {
+        let files = (
+            inner.fd_table[read_fd].take(),
+            inner.fd_table[write_fd].take(),
+        );
+        drop(inner);
+        drop(files);
+        return Err(err.into());
+    }
+    Ok(0)
 }
 
-pub fn sys_dup(fd: usize) -> isize {
-    let task = current_task().unwrap();
-    let mut inner = task.inner_exclusive_access();
-    if fd >= inner.fd_table.len() {
-        return -1;
-    }
-    if inner.fd_table[fd].is_none() {
-        return -1;
-    }
+pub fn sys_dup(fd: usize) -> SysResult {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let file = inner.get_file(fd).ok_or(Errno::EBADF)?;
     let new_fd = inner.alloc_fd();
-    inner.fd_table[new_fd] = Some(Arc::clone(inner.fd_table[fd].as_ref().unwrap()));
-    new_fd as isize
+    inner.fd_table[new_fd] = Some(file);
+    Ok(new_fd as isize)
 }
@@ -1,8 +1,18 @@
+use super::{Errno, SysResult};
+use crate::config::{PAGE_SIZE, USER_SPACE_BOTTOM, USER_SPACE_TOP, USER_STACK_SIZE};
 use crate::fs::{open_file, OpenFlags};
-use crate::mm::{translated_ref, translated_refmut, translated_str};
+use crate::mm::{read_user_str, BadAddress, MapPermission, MemorySet, UserPtr};
 use crate::task::{
-    add_task, current_task, current_user_token, exit_current_and_run_next, pid2task,
-    suspend_current_and_run_next, SignalAction, SignalFlags, MAX_SIG,
+    add_task, all_processes, block_current_and_run_next, current_fatal_pending, current_process,
+    current_signal_pending, current_sigreturn, current_task, exit_current_and_run_next,
+    pid2process, processes_in_group, send_group_signal, send_signal, suspend_current_and_run_next,
+    ProcessControlBlock, SchedPolicy, SigInfo, SignalAction, SignalActionFlags, SignalFlags,
+    SignalStack, TaskControlBlock, INITPROC, MAX_NICE, MAX_SIG, MINSIGSTKSZ, MIN_NICE, SS_DISABLE,
+    SS_ONSTACK,
+};
+use crate::timer::{
+    add_real_timer, add_timer, get_time_us, remove_real_timer, remove_timer, ITimerVal,
+    IntervalTimer, TimeSpec, TimeVal, ITIMER_PROF, ITIMER_REAL,
 };
 use alloc::string::String;
 use alloc::sync::Arc;
@@ -16,23 +26,201 @@ pub fn sys_exit(exit_code: i32) -> ! {
     panic!("Unreachable in sys_exit!");
 }
 
-pub fn sys_yield() -> isize {
+pub fn sys_yield() -> SysResult {
     suspend_current_and_run_next();
-    0
+    Ok(0)
+}
+
+pub fn sys_get_time() -> SysResult {
+    Ok(Time::now().to_msec() as isize)
+}
+
+/// Clock ticks per second of `times`, which is fixed for the user.
+const USER_HZ: usize = 100;
+
+/// `struct tms` of Linux, in clock ticks.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Tms {
+    pub utime: usize,
+    pub stime: usize,
+    pub cutime: usize,
+    pub cstime: usize,
+}
+
+fn us_to_clock_ticks(us: usize) -> usize {
+    us / (1_000_000 / USER_HZ)
+}
+
+/// Store the time used by the process and its waited children, and return
+/// the clock ticks since boot.
+pub fn sys_times(tms: *mut Tms) -> SysResult {
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().charge_time(false);
+    let mut inner = task.process.inner_exclusive_access();
+    let (utime_us, stime_us) = inner.times();
+    let times = Tms {
+        utime: us_to_clock_ticks(utime_us),
+        stime: us_to_clock_ticks(stime_us),
+        cutime: us_to_clock_ticks(inner.cutime_us),
+        cstime: us_to_clock_ticks(inner.cstime_us),
+    };
+    let tms = UserPtr::new(tms as *const Tms);
+    if !tms.is_null() {
+        tms.write(&mut inner.memory_set, times)?;
+    }
+    Ok(us_to_clock_ticks(get_time_us()) as isize)
+}
+
+const RUSAGE_SELF: isize = 0;
+const RUSAGE_CHILDREN: isize = -1;
+const RUSAGE_THREAD: isize = 1;
+
+/// `struct rusage` of Linux. Only the times are counted.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rusage {
+    pub utime: TimeVal,
+    pub stime: TimeVal,
+    // maxrss, ixrss, idrss, isrss, minflt, majflt, nswap, inblock, oublock,
+    // msgsnd, msgrcv, nsignals, nvcsw and nivcsw
+    pub others: [isize; 14],
 }
 
-pub fn sys_get_time() -> isize {
-    Time::now().to_msec() as isize
+pub fn sys_getrusage(who: isize, usage: *mut Rusage) -> SysResult {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.charge_time(false);
+    let thread_times = (task_inner.utime_us, task_inner.stime_us);
+    drop(task_inner);
+    let mut inner = task.process.inner_exclusive_access();
+    let (utime_us, stime_us) = match who {
+        RUSAGE_SELF => inner.times(),
+        RUSAGE_CHILDREN => (inner.cutime_us, inner.cstime_us),
+        RUSAGE_THREAD => thread_times,
+        _ => return Err(Errno::EINVAL),
+    };
+    let rusage = Rusage {
+        utime: TimeVal::from_us(utime_us),
+        stime: TimeVal::from_us(stime_us),
+        ..Default::default()
+    };
+    UserPtr::new(usage as *const Rusage).write(&mut inner.memory_set, rusage)?;
+    Ok(0)
+}
+
+const CLOCK_REALTIME: usize = 0;
+const CLOCK_MONOTONIC: usize = 1;
+/// `req` of clock_nanosleep is an absolute time rather than a duration.
+const TIMER_ABSTIME: usize = 1;
+
+/// Set the interval timer `which` of the current process unless `value` is
+/// null, a zero value disarming it, and store the previous one unless
+/// `old_value` is null. It raises SIGALRM, SIGVTALRM or SIGPROF.
+pub fn sys_setitimer(
+    which: usize,
+    value: *const ITimerVal,
+    old_value: *mut ITimerVal,
+) -> SysResult {
+    if which > ITIMER_PROF {
+        return Err(Errno::EINVAL);
+    }
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let now = inner.itimer_now(which);
+    let prev_value = inner.itimers[which].value(now);
+    let value = UserPtr::new(value);
+    if !value.is_null() {
+        let value = value.read(&mut inner.memory_set)?;
+        let timer = IntervalTimer::new(now, &value).ok_or(Errno::EINVAL)?;
+        inner.itimers[which] = timer;
+        if which == ITIMER_REAL {
+            remove_real_timer(&process);
+            if timer.armed() {
+                add_real_timer(timer.expire_us, &process);
+            }
+        }
+    }
+    let old_value = UserPtr::new(old_value as *const ITimerVal);
+    if !old_value.is_null() {
+        old_value.write(&mut inner.memory_set, prev_value)?;
+    }
+    Ok(0)
+}
+
+/// Store the time left before the interval timer `which` of the current
+/// process expires, and its interval.
+pub fn sys_getitimer(which: usize, value: *mut ITimerVal) -> SysResult {
+    if which > ITIMER_PROF {
+        return Err(Errno::EINVAL);
+    }
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let now = inner.itimer_now(which);
+    let curr_value = inner.itimers[which].value(now);
+    UserPtr::new(value as *const ITimerVal).write(&mut inner.memory_set, curr_value)?;
+    Ok(0)
+}
+
+/// Block until the time reaches `expire_us`, or fail with EINTR when an
+/// unmasked signal arrives first.
+fn sleep_until(expire_us: usize) -> SysResult {
+    let task = current_task().unwrap();
+    while get_time_us() < expire_us {
+        if current_signal_pending() || current_fatal_pending() {
+            return Err(Errno::EINTR);
+        }
+        add_timer(expire_us, task.clone());
+        block_current_and_run_next();
+        // we may be woken up before the timer fires
+        remove_timer(&task);
+    }
+    Ok(0)
+}
+
+pub fn sys_nanosleep(req: *const TimeSpec, rem: *mut TimeSpec) -> SysResult {
+    sys_clock_nanosleep(CLOCK_MONOTONIC, 0, req, rem)
+}
+
+/// Both clocks count from boot since there is no real time clock yet.
+pub fn sys_clock_nanosleep(
+    clock_id: usize,
+    flags: usize,
+    req: *const TimeSpec,
+    rem: *mut TimeSpec,
+) -> SysResult {
+    if clock_id != CLOCK_REALTIME && clock_id != CLOCK_MONOTONIC {
+        return Err(Errno::EINVAL);
+    }
+    if flags & !TIMER_ABSTIME != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let process = current_process();
+    let req = UserPtr::new(req).read(&mut process.inner_exclusive_access().memory_set)?;
+    let req_us = req.to_us().ok_or(Errno::EINVAL)?;
+    let expire_us = if flags & TIMER_ABSTIME != 0 {
+        req_us
+    } else {
+        get_time_us() + req_us
+    };
+    let result = sleep_until(expire_us);
+    let rem = UserPtr::new(rem as *const TimeSpec);
+    if result == Err(Errno::EINTR) && flags & TIMER_ABSTIME == 0 && !rem.is_null() {
+        let remain = TimeSpec::from_us(expire_us.saturating_sub(get_time_us()));
+        rem.write(&mut process.inner_exclusive_access().memory_set, remain)?;
+    }
+    result
 }
 
-pub fn sys_getpid() -> isize {
-    current_task().unwrap().pid.0 as isize
+pub fn sys_getpid() -> SysResult {
+    Ok(current_process().getpid() as isize)
 }
 
-pub fn sys_fork() -> isize {
+pub fn sys_fork() -> SysResult {
     let current_task = current_task().unwrap();
-    let new_task = current_task.fork();
-    let new_pid = new_task.pid.0;
+    let new_process = current_task.process.fork(&current_task);
+    let new_pid = new_process.getpid();
+    let new_task = new_process.inner_exclusive_access().get_task(0).unwrap();
     // modify trap context of new_task, because it returns immediately after switching
     let trap_cx = new_task.inner_exclusive_access().get_trap_cx();
     // we do not have to move to next instruction since we have done it before
@@ -40,155 +228,633 @@ pub fn sys_fork() -> isize {
     trap_cx[TrapFrameArgs::RET] = 0;
     // add new task to scheduler
     add_task(new_task);
-    new_pid as isize
+    Ok(new_pid as isize)
 }
 
-pub fn sys_exec(path: *const u8, mut args: *const usize) -> isize {
-    let token = current_user_token();
-    info!("sys_exec: {:p}  args: {:p}", path, args);
-    // log::info!("path: {:p} token: {:#x} kernel token: {:#x}", path, current_user_token(), kernel_page_table_token());
-    let path = translated_str(token, path);
+/// Load the path and the null-terminated argument list of exec.
+fn read_exec_args(
+    memory_set: &mut MemorySet,
+    path: *const u8,
+    args: *const usize,
+) -> Result<(String, Vec<String>), BadAddress> {
+    let path = read_user_str(memory_set, path)?;
     let mut args_vec: Vec<String> = Vec::new();
+    let mut args = UserPtr::new(args);
     loop {
-        let arg_str_ptr = *translated_ref(token, args);
+        let arg_str_ptr = args.read(memory_set)?;
         if arg_str_ptr == 0 {
             break;
         }
-        args_vec.push(translated_str(token, arg_str_ptr as *const u8));
-        unsafe {
-            args = args.add(1);
+        args_vec.push(read_user_str(memory_set, arg_str_ptr as *const u8)?);
+        args = args.add(1);
+    }
+    Ok((path, args_vec))
+}
+
+pub fn sys_exec(path: *const u8, args: *const usize) -> SysResult {
+    info!("sys_exec: {:p}  args: {:p}", path, args);
+    let task = current_task().unwrap();
+    let process = task.process.clone();
+    // the other threads would lose their program
+    if task.tid != 0 || process.inner_exclusive_access().alive_threads != 1 {
+        return Err(Errno::EBUSY);
+    }
+    let (path, args_vec) =
+        read_exec_args(&mut process.inner_exclusive_access().memory_set, path, args)?;
+    // arguments are pushed on the new user stack
+    let args_size: usize = args_vec
+        .iter()
+        .map(|arg| arg.len() + 1 + core::mem::size_of::<usize>())
+        .sum();
+    if args_size > USER_STACK_SIZE / 2 {
+        return Err(Errno::E2BIG);
+    }
+    let app_inode = open_file(path.as_str(), OpenFlags::RDONLY).ok_or(Errno::ENOENT)?;
+    let all_data = app_inode.read_all();
+    let argc = args_vec.len();
+    process.exec(&task, all_data.as_slice(), args_vec);
+    // return argc because cx.x[10] will be covered with it later
+    Ok(argc as isize)
+}
+
+/// Return immediately instead of blocking if no child has exited yet.
+const WNOHANG: u32 = 1;
+/// Report stopped children too.
+const WUNTRACED: u32 = 2;
+/// Report children continued by SIGCONT too.
+const WCONTINUED: u32 = 8;
+/// The status of a continued child.
+const CONTINUED_STATUS: i32 = 0xffff;
+
+/// Wait for the child `pid`, any child if `pid` is -1, the children in the
+/// process group of the caller if it is 0, or those in the group `-pid`.
+/// If there is not such a child process, return ECHILD.
+/// Else block until such a child exits, or return 0 at once under WNOHANG.
+/// Under WUNTRACED a stop of the child is reported once, with the status
+/// `0x7f | signal << 8`, and so is its continuation under WCONTINUED.
+/// A signal interrupts the wait, which goes on after the handler only with
+/// SA_RESTART.
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32, options: u32) -> SysResult {
+    if options & !(WNOHANG | WUNTRACED | WCONTINUED) != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let task = current_task().unwrap();
+    let process = task.process.clone();
+    loop {
+        // checked before the PCB is locked, a signal sent later wakes us
+        let interrupted = current_signal_pending() || current_fatal_pending();
+        // find a child process
+
+        // ---- access current PCB exclusively
+        let mut inner = process.inner_exclusive_access();
+        let pgid = inner.pgid;
+        let wanted = |p: &Arc<ProcessControlBlock>| match pid {
+            -1 => true,
+            0 => p.inner_exclusive_access().pgid == pgid,
+            _ if pid < 0 => p.inner_exclusive_access().pgid == pid.unsigned_abs(),
+            _ => p.getpid() == pid as usize,
+        };
+        if !inner.children.iter().any(wanted) {
+            return Err(Errno::ECHILD);
+            // ---- release current PCB
+        }
+        let pair = inner.children.iter().enumerate().find(|(_, p)| {
+            // ++++ temporarily access child PCB exclusively
+            wanted(p) && p.inner_exclusive_access().is_zombie
+            // ++++ release child PCB
+        });
+        if let Some((idx, _)) = pair {
+            // the child is deallocated once its hart has switched away from it
+            let child = inner.children.remove(idx);
+            let found_pid = child.getpid();
+            // ++++ temporarily access child PCB exclusively
+            let child_inner = child.inner_exclusive_access();
+            let exit_code = child_inner.exit_code;
+            // the time of the child and the children it has waited for
+            inner.cutime_us += child_inner.utime_us + child_inner.cutime_us;
+            inner.cstime_us += child_inner.stime_us + child_inner.cstime_us;
+            drop(child_inner);
+            // ++++ release child PCB
+            let exit_code_ptr = UserPtr::new(exit_code_ptr as *const i32);
+            if !exit_code_ptr.is_null() {
+                exit_code_ptr.write(&mut inner.memory_set, exit_code)?;
+            }
+            return Ok(found_pid as isize);
         }
+        if options & WUNTRACED != 0 {
+            let stopped = inner.children.iter().find_map(|p| {
+                if !wanted(p) {
+                    return None;
+                }
+                // ++++ temporarily access child PCB exclusively
+                let status = p.inner_exclusive_access().stop_status.take()?;
+                Some((p.getpid(), status))
+            });
+            if let Some((found_pid, status)) = stopped {
+                let exit_code_ptr = UserPtr::new(exit_code_ptr as *const i32);
+                if !exit_code_ptr.is_null() {
+                    exit_code_ptr.write(&mut inner.memory_set, status)?;
+                }
+                return Ok(found_pid as isize);
+            }
+        }
+        if options & WCONTINUED != 0 {
+            let continued = inner.children.iter().find_map(|p| {
+                if !wanted(p) {
+                    return None;
+                }
+                // ++++ temporarily access child PCB exclusively
+                let continued = core::mem::take(&mut p.inner_exclusive_access().continued);
+                continued.then(|| p.getpid())
+            });
+            if let Some(found_pid) = continued {
+                let exit_code_ptr = UserPtr::new(exit_code_ptr as *const i32);
+                if !exit_code_ptr.is_null() {
+                    exit_code_ptr.write(&mut inner.memory_set, CONTINUED_STATUS)?;
+                }
+                return Ok(found_pid as isize);
+            }
+        }
+        if options & WNOHANG != 0 {
+            return Ok(0);
+        }
+        // a pending signal or the exit of the process interrupts the wait
+        if interrupted {
+            return Err(Errno::ERESTARTSYS);
+        }
+        // ---- release current PCB once in the queue
+        process.child_exit.wait_with(inner);
+    }
+}
+
+/// Move the program break to `addr` and return the new break.
+/// Passing 0 queries the current break.
+pub fn sys_brk(addr: usize) -> SysResult {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    Ok(inner.memory_set.brk(addr) as isize)
+}
+
+/// Translate `PROT_READ | PROT_WRITE | PROT_EXEC` into a user permission.
+fn prot_to_perm(prot: usize) -> Result<MapPermission, Errno> {
+    if prot & !0x7 != 0 {
+        return Err(Errno::EINVAL);
     }
-    if let Some(app_inode) = open_file(path.as_str(), OpenFlags::RDONLY) {
-        let all_data = app_inode.read_all();
-        let task = current_task().unwrap();
-        let argc = args_vec.len();
-        task.exec(all_data.as_slice(), args_vec);
-        // return argc because cx.x[10] will be covered with it later
-        argc as isize
+    MapPermission::from_bits((prot << 1) as u8)
+        .map(|perm| perm | MapPermission::U)
+        .ok_or(Errno::EINVAL)
+}
+
+/// Map `len` bytes of zeroed memory at `start`, or anywhere if `start` is 0.
+/// A fixed range must lie in the user space, away from page 0 and the
+/// sigreturn trampoline.
+/// Check that `[start, start + len)` lies in the user space.
+fn check_user_range(start: usize, len: usize) -> Result<(), Errno> {
+    let end = start.checked_add(len).ok_or(Errno::EINVAL)?;
+    if start < USER_SPACE_BOTTOM || end > USER_SPACE_TOP {
+        return Err(Errno::EINVAL);
+    }
+    Ok(())
+}
+
+pub fn sys_mmap(start: usize, len: usize, prot: usize) -> SysResult {
+    if start % PAGE_SIZE != 0 || len == 0 {
+        return Err(Errno::EINVAL);
+    }
+    if start != 0 {
+        check_user_range(start, len)?;
+    }
+    let perm = prot_to_perm(prot)?;
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let start = inner
+        .memory_set
+        .mmap(start, len, perm)
+        .ok_or(Errno::ENOMEM)?;
+    Ok(start as isize)
+}
+
+pub fn sys_munmap(start: usize, len: usize) -> SysResult {
+    if start % PAGE_SIZE != 0 || len == 0 {
+        return Err(Errno::EINVAL);
+    }
+    check_user_range(start, len)?;
+    let process = current_process();
+    process
+        .inner_exclusive_access()
+        .memory_set
+        .munmap(start, len);
+    Ok(0)
+}
+
+pub fn sys_mprotect(start: usize, len: usize, prot: usize) -> SysResult {
+    if start % PAGE_SIZE != 0 {
+        return Err(Errno::EINVAL);
+    }
+    check_user_range(start, len)?;
+    let perm = prot_to_perm(prot)?;
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    if inner.memory_set.mprotect(start, len, perm) {
+        Ok(0)
     } else {
-        -1
+        Err(Errno::ENOMEM)
     }
 }
 
-/// If there is not a child process whose pid is same as given, return -1.
-/// Else if there is a child process but it is still running, return -2.
-pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
-    let task = current_task().unwrap();
-    // find a child process
+/// The threads of process `pid`, where 0 means the current process.
+fn threads_of(pid: usize) -> Result<Vec<Arc<TaskControlBlock>>, Errno> {
+    let process = match pid {
+        0 => current_process(),
+        _ => pid2process(pid).ok_or(Errno::ESRCH)?,
+    };
+    let inner = process.inner_exclusive_access();
+    let tasks: Vec<_> = inner.tasks.iter().flatten().cloned().collect();
+    if tasks.is_empty() {
+        // the process has exited
+        return Err(Errno::ESRCH);
+    }
+    Ok(tasks)
+}
+
+const PRIO_PROCESS: usize = 0;
+
+/// Set the nice value of every thread of the process.
+pub fn sys_setpriority(which: usize, who: usize, nice: i32) -> SysResult {
+    if which != PRIO_PROCESS {
+        return Err(Errno::EINVAL);
+    }
+    for task in threads_of(who)? {
+        task.inner_exclusive_access().sched.nice = nice.clamp(MIN_NICE, MAX_NICE);
+    }
+    Ok(0)
+}
+
+/// Return `20 - nice` of the first thread like Linux, so that the result is
+/// never negative.
+pub fn sys_getpriority(which: usize, who: usize) -> SysResult {
+    if which != PRIO_PROCESS {
+        return Err(Errno::EINVAL);
+    }
+    let nice = threads_of(who)?[0].inner_exclusive_access().sched.nice;
+    Ok((20 - nice) as isize)
+}
 
+/// `param` points to the static priority, which must be 0 for the policies
+/// we have.
+pub fn sys_sched_setscheduler(pid: usize, policy: usize, param: *const i32) -> SysResult {
+    let policy = SchedPolicy::from_raw(policy).ok_or(Errno::EINVAL)?;
+    let process = current_process();
+    let priority = UserPtr::new(param).read(&mut process.inner_exclusive_access().memory_set)?;
+    if priority != 0 {
+        return Err(Errno::EINVAL);
+    }
+    for task in threads_of(pid)? {
+        task.inner_exclusive_access().sched.policy = policy;
+    }
+    Ok(0)
+}
+
+pub fn sys_sched_getscheduler(pid: usize) -> SysResult {
+    let policy = threads_of(pid)?[0].inner_exclusive_access().sched.policy;
+    Ok(policy as isize)
+}
+
+/// Send signal `signum` to process `pid`, to every process in group `-pid`
+/// if it is less than -1, to the group of the caller if it is 0, or to all
+/// the processes except initproc and the caller if it is -1.
+pub fn sys_kill(pid: isize, signum: i32) -> SysResult {
+    if signum < 0 || signum as usize > MAX_SIG {
+        return Err(Errno::EINVAL);
+    }
+    let flag = SignalFlags::from_bits(1 << signum).ok_or(Errno::EINVAL)?;
+    let current = current_process();
+    let info = SigInfo::user(flag, current.getpid());
+    if pid > 0 {
+        let process = pid2process(pid as usize).ok_or(Errno::ESRCH)?;
+        // a real-time signal may not be queued
+        if !send_signal(&process, info) {
+            return Err(Errno::EAGAIN);
+        }
+        return Ok(0);
+    }
+    let pgid = current.inner_exclusive_access().pgid;
+    let sent = match pid {
+        0 => send_group_signal(pgid, info),
+        -1 => {
+            let processes: Vec<_> = all_processes()
+                .into_iter()
+                .filter(|process| {
+                    !Arc::ptr_eq(process, &*INITPROC) && !Arc::ptr_eq(process, &current)
+                })
+                .collect();
+            for process in processes.iter() {
+                send_signal(process, info);
+            }
+            processes.len()
+        }
+        _ => send_group_signal(pid.unsigned_abs(), info),
+    };
+    if sent == 0 {
+        return Err(Errno::ESRCH);
+    }
+    Ok(0)
+}
+
+/// Queue signal `signum` with `value` to process `pid`. A real-time signal is
+/// handled as many times as it is queued, each time with its value.
+pub fn sys_sigqueue(pid: usize, signum: i32, value: usize) -> SysResult {
+    if signum < 0 || signum as usize > MAX_SIG {
+        return Err(Errno::EINVAL);
+    }
+    let flag = SignalFlags::from_bits(1 << signum).ok_or(Errno::EINVAL)?;
+    let process = pid2process(pid).ok_or(Errno::ESRCH)?;
+    let info = SigInfo::queue(flag, current_process().getpid(), value);
+    if !send_signal(&process, info) {
+        return Err(Errno::EAGAIN);
+    }
+    Ok(0)
+}
+
+/// Move process `pid`, the caller itself or one of its children, to group
+/// `pgid` of the same session. 0 means the caller for `pid` and `pid` for
+/// `pgid`.
+pub fn sys_setpgid(pid: usize, pgid: usize) -> SysResult {
+    let process = current_process();
+    let pid = if pid == 0 { process.getpid() } else { pid };
+    let pgid = if pgid == 0 { pid } else { pgid };
+    let sid = process.inner_exclusive_access().sid;
+    // join an existing group, or create one named after the process
+    if pgid != pid
+        && !processes_in_group(pgid)
+            .iter()
+            .any(|process| process.inner_exclusive_access().sid == sid)
+    {
+        return Err(Errno::EPERM);
+    }
     // ---- access current PCB exclusively
-    let mut inner = task.inner_exclusive_access();
-    if !inner
+    let mut inner = process.inner_exclusive_access();
+    if pid == process.getpid() {
+        if inner.sid == pid {
+            // a session leader can't leave its group
+            return Err(Errno::EPERM);
+        }
+        inner.pgid = pgid;
+        return Ok(0);
+    }
+    let child = inner
         .children
         .iter()
-        .any(|p| pid == -1 || pid as usize == p.getpid())
-    {
-        return -1;
-        // ---- release current PCB
-    }
-    let pair = inner.children.iter().enumerate().find(|(_, p)| {
-        // ++++ temporarily access child PCB exclusively
-        p.inner_exclusive_access().is_zombie() && (pid == -1 || pid as usize == p.getpid())
-        // ++++ release child PCB
-    });
-    if let Some((idx, _)) = pair {
-        let child = inner.children.remove(idx);
-        // confirm that child will be deallocated after being removed from children list
-        assert_eq!(Arc::strong_count(&child), 1);
-        let found_pid = child.getpid();
-        // ++++ temporarily access child PCB exclusively
-        let exit_code = child.inner_exclusive_access().exit_code;
-        // ++++ release child PCB
-        *translated_refmut(inner.memory_set.token(), exit_code_ptr) = exit_code;
-        found_pid as isize
-    } else {
-        -2
+        .find(|child| child.getpid() == pid)
+        .ok_or(Errno::ESRCH)?;
+    // ++++ access child PCB exclusively
+    let mut child_inner = child.inner_exclusive_access();
+    if child_inner.sid != sid || child_inner.sid == pid {
+        return Err(Errno::EPERM);
     }
-    // ---- release current PCB automatically
+    child_inner.pgid = pgid;
+    Ok(0)
 }
 
-pub fn sys_kill(pid: usize, signum: i32) -> isize {
-    if let Some(task) = pid2task(pid) {
-        if let Some(flag) = SignalFlags::from_bits(1 << signum) {
-            // insert the signal if legal
-            let mut task_ref = task.inner_exclusive_access();
-            if task_ref.signals.contains(flag) {
-                return -1;
-            }
-            task_ref.signals.insert(flag);
-            0
-        } else {
-            -1
-        }
+pub fn sys_getpgid(pid: usize) -> SysResult {
+    let process = if pid == 0 {
+        current_process()
+    } else {
+        pid2process(pid).ok_or(Errno::ESRCH)?
+    };
+    let pgid = process.inner_exclusive_access().pgid;
+    Ok(pgid as isize)
+}
+
+pub fn sys_getsid(pid: usize) -> SysResult {
+    let process = if pid == 0 {
+        current_process()
     } else {
-        -1
+        pid2process(pid).ok_or(Errno::ESRCH)?
+    };
+    let sid = process.inner_exclusive_access().sid;
+    Ok(sid as isize)
+}
+
+/// Start a new session and a new group led by the caller, which must not
+/// lead a group. Return the new session id.
+pub fn sys_setsid() -> SysResult {
+    let process = current_process();
+    let pid = process.getpid();
+    let mut inner = process.inner_exclusive_access();
+    if inner.pgid == pid {
+        return Err(Errno::EPERM);
+    }
+    inner.pgid = pid;
+    inner.sid = pid;
+    Ok(pid as isize)
+}
+
+/// How `sigprocmask` changes the mask with its set: add, remove or replace.
+const SIG_BLOCK: usize = 0;
+const SIG_UNBLOCK: usize = 1;
+const SIG_SETMASK: usize = 2;
+
+/// Change the signal mask of the current thread with `set` as `how` says
+/// unless `set` is null, and store the previous one unless `old_set` is null.
+pub fn sys_sigprocmask(how: usize, set: *const u64, old_set: *mut u64) -> SysResult {
+    let task = current_task().unwrap();
+    let process = task.process.clone();
+    let mut process_inner = process.inner_exclusive_access();
+    let mut task_inner = task.inner_exclusive_access();
+    let old_mask = task_inner.signal_mask;
+    let set = UserPtr::new(set);
+    if !set.is_null() {
+        let set = SignalFlags::from_bits_truncate(set.read(&mut process_inner.memory_set)?);
+        let mask = match how {
+            SIG_BLOCK => old_mask | set,
+            SIG_UNBLOCK => old_mask - set,
+            SIG_SETMASK => set,
+            _ => return Err(Errno::EINVAL),
+        };
+        // SIGKILL and SIGSTOP can't be blocked, which is not an error
+        task_inner.signal_mask = mask - SignalFlags::unblockable();
+    }
+    let old_set = UserPtr::new(old_set as *const u64);
+    if !old_set.is_null() {
+        old_set.write(&mut process_inner.memory_set, old_mask.bits())?;
+    }
+    Ok(0)
+}
+
+/// Store the signals which are pending on the current thread but blocked.
+pub fn sys_sigpending(set: *mut u64) -> SysResult {
+    let task = current_task().unwrap();
+    let process = task.process.clone();
+    let mut process_inner = process.inner_exclusive_access();
+    let task_inner = task.inner_exclusive_access();
+    let pending =
+        (process_inner.pending.signals | task_inner.pending.signals) & task_inner.signal_mask;
+    drop(task_inner);
+    UserPtr::new(set as *const u64).write(&mut process_inner.memory_set, pending.bits())?;
+    Ok(0)
+}
+
+/// Replace the signal mask of the current thread with `mask` and block until
+/// a signal arrives, then fail with EINTR. The previous mask comes back once
+/// the handler of the signal returns.
+pub fn sys_sigsuspend(mask: *const u64) -> SysResult {
+    let task = current_task().unwrap();
+    let process = task.process.clone();
+    let mut process_inner = process.inner_exclusive_access();
+    let mask = UserPtr::new(mask).read(&mut process_inner.memory_set)?;
+    let mut task_inner = task.inner_exclusive_access();
+    let saved_mask = task_inner.signal_mask;
+    task_inner.signal_mask = SignalFlags::from_bits_truncate(mask) - SignalFlags::unblockable();
+    task_inner.saved_mask = Some(saved_mask);
+    drop(task_inner);
+    drop(process_inner);
+    while !current_signal_pending() && !current_fatal_pending() {
+        block_current_and_run_next();
     }
+    Err(Errno::EINTR)
 }
 
-pub fn sys_sigprocmask(mask: u32) -> isize {
-    if let Some(task) = current_task() {
-        let mut inner = task.inner_exclusive_access();
-        let old_mask = inner.signal_mask;
-        if let Some(flag) = SignalFlags::from_bits(mask) {
-            inner.signal_mask = flag;
-            old_mask.bits() as isize
+/// Wait for one of the signals in `set`, which should be blocked, and take
+/// it from the pending ones without running its handler. Store its
+/// `SigInfo` unless `info` is null and return its number. Fail with EAGAIN
+/// once `timeout` has passed unless it is null, or with EINTR when another
+/// signal arrives first.
+pub fn sys_sigtimedwait(
+    set: *const u64,
+    info: *mut SigInfo,
+    timeout: *const TimeSpec,
+) -> SysResult {
+    let task = current_task().unwrap();
+    let process = task.process.clone();
+    let (set, expire_us) = {
+        let mut process_inner = process.inner_exclusive_access();
+        let set = UserPtr::new(set).read(&mut process_inner.memory_set)?;
+        let timeout = UserPtr::new(timeout);
+        let expire_us = if timeout.is_null() {
+            None
         } else {
-            -1
+            let timeout = timeout.read(&mut process_inner.memory_set)?;
+            Some(get_time_us() + timeout.to_us().ok_or(Errno::EINVAL)?)
+        };
+        (
+            SignalFlags::from_bits_truncate(set) - SignalFlags::unblockable(),
+            expire_us,
+        )
+    };
+    loop {
+        let mut process_inner = process.inner_exclusive_access();
+        let mut task_inner = task.inner_exclusive_access();
+        let pending = (process_inner.pending.signals | task_inner.pending.signals) & set;
+        if !pending.is_empty() {
+            // the lowest one, of the thread first
+            let signal = SignalFlags::from_bits(1 << pending.signum()).unwrap();
+            let signal_info = task_inner
+                .pending
+                .pop(signal)
+                .or_else(|| process_inner.pending.pop(signal))
+                .unwrap();
+            drop(task_inner);
+            let info = UserPtr::new(info as *const SigInfo);
+            if !info.is_null() {
+                info.write(&mut process_inner.memory_set, signal_info)?;
+            }
+            return Ok(signal.signum() as isize);
         }
-    } else {
-        -1
+        drop(task_inner);
+        drop(process_inner);
+        if current_signal_pending() || current_fatal_pending() {
+            return Err(Errno::EINTR);
+        }
+        if let Some(expire_us) = expire_us {
+            if get_time_us() >= expire_us {
+                return Err(Errno::EAGAIN);
+            }
+            add_timer(expire_us, task.clone());
+        }
+        block_current_and_run_next();
+        // we may be woken up before the timer fires
+        remove_timer(&task);
     }
 }
 
-pub fn sys_sigreturn() -> isize {
-    if let Some(task) = current_task() {
-        let mut inner = task.inner_exclusive_access();
-        inner.handling_sig = -1;
-        // restore the trap context
-        let trap_ctx = inner.get_trap_cx();
-        *trap_ctx = inner.trap_ctx_backup.clone().unwrap();
-        // Here we return the value of a0 in the trap_ctx,
-        // otherwise it will be overwritten after we trap
-        // back to the original execution of the application.
-        trap_ctx[TrapFrameArgs::RET] as isize
-    } else {
-        -1
+/// Set the alternate stack of the current thread unless `stack` is null, and
+/// store the previous one unless `old_stack` is null. The stack can't change
+/// while the thread runs on it.
+pub fn sys_sigaltstack(stack: *const SignalStack, old_stack: *mut SignalStack) -> SysResult {
+    let task = current_task().unwrap();
+    let process = task.process.clone();
+    let mut process_inner = process.inner_exclusive_access();
+    let mut task_inner = task.inner_exclusive_access();
+    let sp = task_inner.get_trap_cx()[TrapFrameArgs::SP];
+    let prev_stack = task_inner.signal_stack;
+    let stack = UserPtr::new(stack);
+    if !stack.is_null() {
+        let mut stack = stack.read(&mut process_inner.memory_set)?;
+        if prev_stack.contains(sp) {
+            return Err(Errno::EPERM);
+        }
+        match stack.flags {
+            SS_DISABLE => stack = SignalStack::disabled(),
+            // SS_ONSTACK is taken for 0 like Linux
+            0 | SS_ONSTACK if stack.size < MINSIGSTKSZ => return Err(Errno::ENOMEM),
+            0 | SS_ONSTACK => stack.flags = 0,
+            _ => return Err(Errno::EINVAL),
+        }
+        task_inner.signal_stack = stack;
     }
+    let old_stack = UserPtr::new(old_stack as *const SignalStack);
+    if !old_stack.is_null() {
+        old_stack.write(&mut process_inner.memory_set, prev_stack.status(sp))?;
+    }
+    Ok(0)
 }
 
-fn check_sigaction_error(signal: SignalFlags, action: usize, old_action: usize) -> bool {
-    if action == 0
-        || old_action == 0
-        || signal == SignalFlags::SIGKILL
-        || signal == SignalFlags::SIGSTOP
-    {
-        true
-    } else {
-        false
+/// Return from the innermost signal handler. The trap handler does it when a
+/// handler returns, but a handler may also call it itself.
+pub fn sys_sigreturn() -> SysResult {
+    if !current_sigreturn() {
+        return Err(Errno::EINVAL);
     }
+    let trap_ctx = current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .get_trap_cx();
+    // Here we return the value of a0 in the trap_ctx,
+    // otherwise it will be overwritten after we trap
+    // back to the original execution of the application.
+    Ok(trap_ctx[TrapFrameArgs::RET] as isize)
 }
 
+/// Set the action for `signum` unless `action` is null, and store the previous
+/// one unless `old_action` is null. SIGKILL and SIGSTOP keep their actions.
 pub fn sys_sigaction(
     signum: i32,
     action: *const SignalAction,
     old_action: *mut SignalAction,
-) -> isize {
-    let token = current_user_token();
-    let task = current_task().unwrap();
-    let mut inner = task.inner_exclusive_access();
-    if signum as usize > MAX_SIG {
-        return -1;
-    }
-    if let Some(flag) = SignalFlags::from_bits(1 << signum) {
-        if check_sigaction_error(flag, action as usize, old_action as usize) {
-            return -1;
-        }
-        let prev_action = inner.signal_actions.table[signum as usize];
-        *translated_refmut(token, old_action) = prev_action;
-        inner.signal_actions.table[signum as usize] = *translated_ref(token, action);
-        0
+) -> SysResult {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    if signum < 0 || signum as usize > MAX_SIG {
+        return Err(Errno::EINVAL);
+    }
+    let flag = SignalFlags::from_bits(1 << signum).ok_or(Errno::EINVAL)?;
+    if flag == SignalFlags::SIGKILL || flag == SignalFlags::SIGSTOP {
+        return Err(Errno::EINVAL);
+    }
+    let prev_action = inner.signal_actions.table[signum as usize];
+    let action = UserPtr::new(action);
+    let mut new_action = if action.is_null() {
+        prev_action
     } else {
-        -1
+        action.read(&mut inner.memory_set)?
+    };
+    // flags we don't know are ignored
+    new_action.flags = SignalActionFlags::from_bits_truncate(new_action.flags.bits());
+    let old_action = UserPtr::new(old_action as *const SignalAction);
+    if !old_action.is_null() {
+        old_action.write(&mut inner.memory_set, prev_action)?;
     }
+    inner.signal_actions.table[signum as usize] = new_action;
+    Ok(0)
 }
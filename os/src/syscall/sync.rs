@@ -0,0 +1,240 @@
+use super::{Errno, SysResult};
+use crate::mm::UserPtr;
+use crate::sync::{futex_wait, futex_wake, Condvar, Mutex, Semaphore};
+use crate::task::{current_fatal_pending, current_process, current_signal_pending, current_task};
+use crate::timer::{get_time_us, TimeSpec};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+const FUTEX_WAIT: usize = 0;
+const FUTEX_WAKE: usize = 1;
+/// The futex is not shared with other processes, which makes no difference
+/// to us.
+const FUTEX_PRIVATE_FLAG: usize = 128;
+
+/// `FUTEX_WAIT` blocks while the word at `uaddr` holds `val`, for at most
+/// `timeout` if it is not null. `FUTEX_WAKE` wakes up at most `val` waiters.
+pub fn sys_futex(uaddr: usize, op: usize, val: u32, timeout: *const TimeSpec) -> SysResult {
+    if uaddr % core::mem::size_of::<u32>() != 0 {
+        return Err(Errno::EINVAL);
+    }
+    match op & !FUTEX_PRIVATE_FLAG {
+        FUTEX_WAIT => {
+            let timeout = UserPtr::new(timeout);
+            let expire_us = if timeout.is_null() {
+                None
+            } else {
+                let timeout =
+                    timeout.read(&mut current_process().inner_exclusive_access().memory_set)?;
+                Some(get_time_us() + timeout.to_us().ok_or(Errno::EINVAL)?)
+            };
+            if current_signal_pending() || current_fatal_pending() {
+                return Err(Errno::EINTR);
+            }
+            if !futex_wait(uaddr, val, expire_us)? {
+                return Err(Errno::EAGAIN);
+            }
+            match expire_us {
+                Some(expire_us) if get_time_us() >= expire_us => Err(Errno::ETIMEDOUT),
+                _ if current_signal_pending() => Err(Errno::EINTR),
+                _ => Ok(0),
+            }
+        }
+        FUTEX_WAKE => Ok(futex_wake(uaddr, val as usize)? as isize),
+        _ => Err(Errno::ENOSYS),
+    }
+}
+
+/// Find a free id in one of the object tables of a process.
+fn alloc_id<T>(list: &mut Vec<Option<T>>) -> usize {
+    if let Some(id) = list.iter().position(|object| object.is_none()) {
+        id
+    } else {
+        list.push(None);
+        list.len() - 1
+    }
+}
+
+pub fn sys_mutex_create() -> SysResult {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let id = alloc_id(&mut inner.mutex_list);
+    inner.mutex_list[id] = Some(Arc::new(Mutex::new()));
+    inner.mutex_banker.add_resource(id, 1);
+    Ok(id as isize)
+}
+
+/// Block until mutex `id` is ours. Fail with `EDEADLK` instead if deadlock
+/// detection is on and the threads may deadlock.
+pub fn sys_mutex_lock(id: usize) -> SysResult {
+    let task = current_task().unwrap();
+    let process = task.process.clone();
+    // ---- access current PCB exclusively
+    let mut inner = process.inner_exclusive_access();
+    let mutex = inner
+        .mutex_list
+        .get(id)
+        .cloned()
+        .flatten()
+        .ok_or(Errno::EINVAL)?;
+    inner.mutex_banker.request(task.tid, id);
+    if inner.deadlock_detect && !inner.mutex_banker.is_safe() {
+        inner.mutex_banker.cancel(task.tid, id);
+        return Err(Errno::EDEADLK);
+    }
+    drop(inner);
+    // ---- release current PCB
+    let locked = mutex.lock();
+    let mut inner = process.inner_exclusive_access();
+    if !locked {
+        inner.mutex_banker.cancel(task.tid, id);
+        return Err(Errno::EINTR);
+    }
+    inner.mutex_banker.acquire(task.tid, id);
+    Ok(0)
+}
+
+pub fn sys_mutex_unlock(id: usize) -> SysResult {
+    let task = current_task().unwrap();
+    let process = task.process.clone();
+    let inner = process.inner_exclusive_access();
+    let mutex = inner
+        .mutex_list
+        .get(id)
+        .cloned()
+        .flatten()
+        .ok_or(Errno::EINVAL)?;
+    // don't wake up the waiters with the PCB locked
+    drop(inner);
+    if !mutex.unlock() {
+        return Err(Errno::EPERM);
+    }
+    process
+        .inner_exclusive_access()
+        .mutex_banker
+        .release(task.tid, id);
+    Ok(0)
+}
+
+/// Create a semaphore with `count` units.
+pub fn sys_semaphore_create(count: usize) -> SysResult {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let id = alloc_id(&mut inner.semaphore_list);
+    inner.semaphore_list[id] = Some(Arc::new(Semaphore::new(count)));
+    inner.semaphore_banker.add_resource(id, count);
+    Ok(id as isize)
+}
+
+pub fn sys_semaphore_up(id: usize) -> SysResult {
+    let task = current_task().unwrap();
+    let mut inner = task.process.inner_exclusive_access();
+    let semaphore = inner
+        .semaphore_list
+        .get(id)
+        .cloned()
+        .flatten()
+        .ok_or(Errno::EINVAL)?;
+    inner.semaphore_banker.release(task.tid, id);
+    drop(inner);
+    semaphore.up();
+    Ok(0)
+}
+
+/// Block until semaphore `id` is positive and take one unit. Fail with
+/// `EDEADLK` instead if deadlock detection is on and the threads may
+/// deadlock.
+pub fn sys_semaphore_down(id: usize) -> SysResult {
+    let task = current_task().unwrap();
+    let process = task.process.clone();
+    // ---- access current PCB exclusively
+    let mut inner = process.inner_exclusive_access();
+    let semaphore = inner
+        .semaphore_list
+        .get(id)
+        .cloned()
+        .flatten()
+        .ok_or(Errno::EINVAL)?;
+    inner.semaphore_banker.request(task.tid, id);
+    if inner.deadlock_detect && !inner.semaphore_banker.is_safe() {
+        inner.semaphore_banker.cancel(task.tid, id);
+        return Err(Errno::EDEADLK);
+    }
+    drop(inner);
+    // ---- release current PCB
+    let got = semaphore.down();
+    let mut inner = process.inner_exclusive_access();
+    if !got {
+        inner.semaphore_banker.cancel(task.tid, id);
+        return Err(Errno::EINTR);
+    }
+    inner.semaphore_banker.acquire(task.tid, id);
+    Ok(0)
+}
+
+pub fn sys_condvar_create() -> SysResult {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let id = alloc_id(&mut inner.condvar_list);
+    inner.condvar_list[id] = Some(Arc::new(Condvar::new()));
+    Ok(id as isize)
+}
+
+pub fn sys_condvar_signal(id: usize) -> SysResult {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let condvar = inner
+        .condvar_list
+        .get(id)
+        .cloned()
+        .flatten()
+        .ok_or(Errno::EINVAL)?;
+    drop(inner);
+    condvar.signal();
+    Ok(0)
+}
+
+/// Unlock mutex `mutex_id` and wait on condvar `id`, then lock the mutex
+/// again. It may return without being signaled.
+pub fn sys_condvar_wait(id: usize, mutex_id: usize) -> SysResult {
+    let task = current_task().unwrap();
+    let process = task.process.clone();
+    // ---- access current PCB exclusively
+    let mut inner = process.inner_exclusive_access();
+    let condvar = inner
+        .condvar_list
+        .get(id)
+        .cloned()
+        .flatten()
+        .ok_or(Errno::EINVAL)?;
+    let mutex = inner
+        .mutex_list
+        .get(mutex_id)
+        .cloned()
+        .flatten()
+        .ok_or(Errno::EINVAL)?;
+    // the mutex is given back while waiting and wanted again after that
+    inner.mutex_banker.release(task.tid, mutex_id);
+    inner.mutex_banker.request(task.tid, mutex_id);
+    drop(inner);
+    // ---- release current PCB
+    let locked = condvar.wait(&mutex);
+    let mut inner = process.inner_exclusive_access();
+    if !locked {
+        inner.mutex_banker.cancel(task.tid, mutex_id);
+        return Err(Errno::EINTR);
+    }
+    inner.mutex_banker.acquire(task.tid, mutex_id);
+    Ok(0)
+}
+
+/// Turn deadlock detection of the current process on with 1 or off with 0.
+pub fn sys_enable_deadlock_detect(enabled: usize) -> SysResult {
+    let enabled = match enabled {
+        0 => false,
+        1 => true,
+        _ => return Err(Errno::EINVAL),
+    };
+    current_process().inner_exclusive_access().deadlock_detect = enabled;
+    Ok(0)
+}
@@ -0,0 +1,80 @@
+use super::{Errno, SysResult};
+use crate::mm::UserPtr;
+use crate::task::{
+    add_task, current_fatal_pending, current_signal_pending, current_task, TaskControlBlock,
+};
+use alloc::sync::Arc;
+use polyhal_trap::trapframe::TrapFrameArgs;
+
+/// Start a thread at `entry` with `arg` as its argument and a new user
+/// stack. Return the tid of the thread.
+pub fn sys_thread_create(entry: usize, arg: usize) -> SysResult {
+    let task = current_task().unwrap();
+    let process = task.process.clone();
+    let task_inner = task.inner_exclusive_access();
+    // inherit the scheduling parameters and the signal mask
+    let sched = task_inner.sched;
+    let signal_mask = task_inner.signal_mask;
+    drop(task_inner);
+    // ---- access current PCB exclusively
+    let mut process_inner = process.inner_exclusive_access();
+    let ustack_top = process_inner.memory_set.alloc_user_stack();
+    let tid = process_inner.alloc_tid();
+    let new_task = Arc::new(TaskControlBlock::new(
+        process.clone(),
+        tid,
+        ustack_top,
+        sched,
+        signal_mask,
+    ));
+    let trap_cx = new_task.inner_exclusive_access().get_trap_cx();
+    trap_cx[TrapFrameArgs::SEPC] = entry;
+    trap_cx[TrapFrameArgs::ARG0] = arg;
+    process_inner.add_thread(new_task.clone());
+    drop(process_inner);
+    // ---- release current PCB
+    add_task(new_task);
+    Ok(tid as isize)
+}
+
+pub fn sys_gettid() -> SysResult {
+    Ok(current_task().unwrap().tid as isize)
+}
+
+/// Block until thread `tid` of the current process exits, then release it
+/// and store its exit code. Return `tid`.
+///
+/// The main thread can't be waited for, the process exits with it.
+pub fn sys_waittid(tid: usize, exit_code_ptr: *mut i32) -> SysResult {
+    let task = current_task().unwrap();
+    if tid == task.tid {
+        return Err(Errno::EDEADLK);
+    }
+    if tid == 0 {
+        return Err(Errno::EINVAL);
+    }
+    let process = task.process.clone();
+    loop {
+        // checked before the PCB is locked, a signal sent later wakes us
+        let interrupted = current_signal_pending() || current_fatal_pending();
+        // ---- access current PCB exclusively
+        let mut inner = process.inner_exclusive_access();
+        let waited = inner.get_task(tid).ok_or(Errno::ESRCH)?;
+        let exit_code = waited.inner_exclusive_access().exit_code;
+        if let Some(exit_code) = exit_code {
+            // the tid can be used by a new thread now
+            inner.tasks[tid] = None;
+            let exit_code_ptr = UserPtr::new(exit_code_ptr as *const i32);
+            if !exit_code_ptr.is_null() {
+                exit_code_ptr.write(&mut inner.memory_set, exit_code)?;
+            }
+            return Ok(tid as isize);
+        }
+        // a pending signal or the exit of the process interrupts the wait
+        if interrupted {
+            return Err(Errno::ERESTARTSYS);
+        }
+        // ---- release current PCB once in the queue
+        process.thread_exit.wait_with(inner);
+    }
+}
@@ -1,18 +1,48 @@
+use super::signal::DefaultAction;
 use crate::task::{SignalFlags, MAX_SIG};
 
+/// Handler values which are not functions: take the default action of the
+/// signal, or discard it.
+pub const SIG_DFL: usize = 0;
+pub const SIG_IGN: usize = 1;
+
+bitflags! {
+    /// How a handler is run, with the values of Linux.
+    pub struct SignalActionFlags: u32 {
+        /// Pass the `SigInfo` and the `UContext` to the handler as well.
+        const SA_SIGINFO = 4;
+        /// Return from the handler to `restorer` instead of the trampoline
+        /// of the kernel.
+        const SA_RESTORER = 0x0400_0000;
+        /// Run the handler on the alternate stack of the thread.
+        const SA_ONSTACK = 0x0800_0000;
+        /// Run the syscall interrupted by the handler again.
+        const SA_RESTART = 0x1000_0000;
+        /// Don't block the signal while its handler runs.
+        const SA_NODEFER = 0x4000_0000;
+    }
+}
+
 /// Action for a signal
 #[repr(C, align(16))]
 #[derive(Debug, Clone, Copy)]
 pub struct SignalAction {
     pub handler: usize,
+    /// Signals blocked while the handler runs, besides the signal itself.
     pub mask: SignalFlags,
+    pub flags: SignalActionFlags,
+    /// Where the handler returns to with SA_RESTORER, which must call
+    /// sigreturn.
+    pub restorer: usize,
 }
 
 impl Default for SignalAction {
     fn default() -> Self {
         Self {
-            handler: 0,
-            mask: SignalFlags::from_bits(40).unwrap(),
+            handler: SIG_DFL,
+            mask: SignalFlags::empty(),
+            flags: SignalActionFlags::empty(),
+            restorer: 0,
         }
     }
 }
@@ -29,3 +59,18 @@ impl Default for SignalActions {
         }
     }
 }
+
+impl SignalActions {
+    /// Whether `signal` is discarded when it is sent, instead of becoming
+    /// pending. SIGCONT has continued the process by then.
+    pub fn ignores(&self, signal: SignalFlags) -> bool {
+        match self.table[signal.signum()].handler {
+            SIG_IGN => true,
+            SIG_DFL => matches!(
+                signal.default_action(),
+                DefaultAction::Ignore | DefaultAction::Continue
+            ),
+            _ => false,
+        }
+    }
+}
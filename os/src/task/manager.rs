@@ -1,56 +1,92 @@
-use super::TaskControlBlock;
-use crate::sync::UPSafeCell;
-use alloc::collections::{BTreeMap, VecDeque};
+use super::scheduler::{new_scheduler, Scheduler};
+use super::{ProcessControlBlock, TaskControlBlock, TaskStatus};
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use lazy_static::*;
 use log::*;
+use polyhal::utils::MutexNoIrq;
 pub struct TaskManager {
-    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+    scheduler: Box<dyn Scheduler>,
 }
 
-/// A simple FIFO scheduler.
+/// The ready tasks, ordered by the scheduler chosen at build time.
+///
+/// The scheduler may lock the TCB of a task while the manager is locked, so
+/// never add a task with its TCB locked.
 impl TaskManager {
     pub fn new() -> Self {
         Self {
-            ready_queue: VecDeque::new(),
+            scheduler: new_scheduler(),
         }
     }
     pub fn add(&mut self, task: Arc<TaskControlBlock>) {
-        self.ready_queue.push_back(task);
+        self.scheduler.add(task);
     }
     pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
-        self.ready_queue.pop_front()
+        self.scheduler.fetch()
     }
 }
 
 lazy_static! {
-    pub static ref TASK_MANAGER: UPSafeCell<TaskManager> =
-        unsafe { UPSafeCell::new(TaskManager::new()) };
-    pub static ref PID2TCB: UPSafeCell<BTreeMap<usize, Arc<TaskControlBlock>>> =
-        unsafe { UPSafeCell::new(BTreeMap::new()) };
+    pub static ref TASK_MANAGER: MutexNoIrq<TaskManager> = MutexNoIrq::new(TaskManager::new());
+    pub static ref PID2PCB: MutexNoIrq<BTreeMap<usize, Arc<ProcessControlBlock>>> =
+        MutexNoIrq::new(BTreeMap::new());
 }
 
 pub fn add_task(task: Arc<TaskControlBlock>) {
     //trace!("os::task::manager::add_task");
-    PID2TCB
-        .exclusive_access()
-        .insert(task.getpid(), Arc::clone(&task));
-    TASK_MANAGER.exclusive_access().add(task);
+    TASK_MANAGER.lock().add(task);
+}
+
+/// Put a blocked task back to the ready queue.
+///
+/// A running task will not block next time it tries, so a wakeup that comes
+/// in before the task has blocked is not lost. Waking up a task more than
+/// once, or a task which is ready, does nothing.
+pub fn wakeup_task(task: Arc<TaskControlBlock>) {
+    let mut task_inner = task.inner_exclusive_access();
+    match task_inner.task_status {
+        TaskStatus::Blocked => {
+            task_inner.task_status = TaskStatus::Ready;
+            // still switching out on some hart, which will queue it then
+            if task_inner.on_cpu {
+                return;
+            }
+        }
+        TaskStatus::Running => {
+            task_inner.wakeup_pending = true;
+            return;
+        }
+        _ => return,
+    }
+    drop(task_inner);
+    add_task(task);
 }
 
 pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
     //trace!("os::task::manager::fetch_task");
-    TASK_MANAGER.exclusive_access().fetch()
+    TASK_MANAGER.lock().fetch()
 }
 
-pub fn pid2task(pid: usize) -> Option<Arc<TaskControlBlock>> {
-    let map = PID2TCB.exclusive_access();
+pub fn pid2process(pid: usize) -> Option<Arc<ProcessControlBlock>> {
+    let map = PID2PCB.lock();
     map.get(&pid).map(Arc::clone)
 }
 
-pub fn remove_from_pid2task(pid: usize) {
-    let mut map = PID2TCB.exclusive_access();
+/// All the processes which have not exited.
+pub fn all_processes() -> Vec<Arc<ProcessControlBlock>> {
+    PID2PCB.lock().values().cloned().collect()
+}
+
+pub fn insert_into_pid2process(pid: usize, process: Arc<ProcessControlBlock>) {
+    PID2PCB.lock().insert(pid, process);
+}
+
+pub fn remove_from_pid2process(pid: usize) {
+    let mut map = PID2PCB.lock();
     if map.remove(&pid).is_none() {
-        panic!("cannot find pid {} in pid2task!", pid);
+        panic!("cannot find pid {} in pid2process!", pid);
     }
 }
@@ -1,34 +1,58 @@
 mod action;
 mod manager;
+mod pending;
 mod pid;
+mod process;
 mod processor;
+mod scheduler;
+mod sigframe;
 mod signal;
 #[allow(clippy::module_inception)]
 mod task;
 
+use crate::config::{SIGRETURN_TRAMPOLINE, USER_STACK_SIZE};
 use crate::fs::{open_file, OpenFlags};
+use crate::mm::{MapPermission, UserPtr};
+use crate::syscall::Errno;
+use crate::timer::{
+    add_real_timer, get_time_us, remove_real_timer, ITIMER_PROF, ITIMER_REAL, ITIMER_VIRTUAL,
+};
 use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::mem::{offset_of, size_of};
 use lazy_static::*;
 use log::*;
 use manager::fetch_task;
-use manager::remove_from_pid2task;
+use manager::remove_from_pid2process;
 use polyhal::instruction::shutdown;
 use polyhal::kcontext::KContext;
 use polyhal_trap::trapframe::TrapFrameArgs;
-use task::{TaskControlBlock, TaskStatus};
+use process::ProcessControlBlockInner;
+use sigframe::{
+    SignalFrame, UContext, CLD_CONTINUED, CLD_EXITED, CLD_KILLED, CLD_STOPPED, SEGV_ACCERR,
+    SEGV_MAPERR,
+};
+use signal::DefaultAction;
+use task::TaskStatus;
 
-pub use action::{SignalAction, SignalActions};
-pub use manager::{add_task, pid2task};
+pub use action::{SignalAction, SignalActionFlags, SignalActions, SIG_DFL, SIG_IGN};
+pub use manager::{add_task, all_processes, pid2process, wakeup_task};
+pub use pending::SignalQueue;
 pub use pid::{pid_alloc, PidHandle};
+pub use process::ProcessControlBlock;
 pub use processor::{
-    current_task, current_user_token, init_kernel_page, run_tasks, schedule, take_current_task,
+    current_process, current_task, current_user_token, enter_user, init_hart, init_kernel_page,
+    leave_user, run_tasks, schedule, shootdown_tlb,
 };
+pub use scheduler::{SchedPolicy, MAX_NICE, MIN_NICE};
+pub use sigframe::{SigInfo, SignalStack, ILL_ILLOPC, MINSIGSTKSZ, SS_DISABLE, SS_ONSTACK};
 pub use signal::{SignalFlags, MAX_SIG};
+pub use task::TaskControlBlock;
 
 pub fn suspend_current_and_run_next() {
     //trace!("os::task::suspend_current_and_run_next");
     // There must be an application running.
-    let task = take_current_task().unwrap();
+    let task = current_task().unwrap();
 
     // ---- access current TCB exclusively
     let mut task_inner = task.inner_exclusive_access();
@@ -38,206 +62,617 @@ pub fn suspend_current_and_run_next() {
     drop(task_inner);
     // ---- release current PCB
 
-    // push back to ready queue.
-    add_task(task);
-    // jump to scheduling cycle
+    drop(task);
+    // jump to scheduling cycle, which pushes the task back to ready queue
+    schedule(task_cx_ptr);
+}
+
+/// Block the current task and run the next task in task list.
+///
+/// The caller must have put the task on some queue beforehand, otherwise
+/// nobody can wake it up again. Returns at once if the task has been woken
+/// up since then.
+pub fn block_current_and_run_next() {
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    if task_inner.wakeup_pending {
+        task_inner.wakeup_pending = false;
+        return;
+    }
+    let task_cx_ptr = &mut task_inner.task_cx as *mut KContext;
+    task_inner.task_status = TaskStatus::Blocked;
+    drop(task_inner);
+    drop(task);
     schedule(task_cx_ptr);
 }
 
 /// pid of usertests app in make run TEST=1
 pub const IDLE_PID: usize = 0;
 
-/// Exit the current 'Running' task and run the next task in task list.
+/// Exit the current thread and run the next task in task list. The whole
+/// process exits with its main thread.
 pub fn exit_current_and_run_next(exit_code: i32) {
     trace!("os::task::exit_current_and_run_next");
-    // take from Processor
-    let task = take_current_task().unwrap();
+    // the Processor keeps the task until it is switched out
+    let task = current_task().unwrap();
+    let process = task.process.clone();
+    if task.tid == 0 {
+        start_process_exit(&process, exit_code);
+    }
 
-    let pid = task.getpid();
-    if pid == IDLE_PID {
-        println!(
-            "[kernel] Idle process exit with exit_code {} ...",
-            exit_code
-        );
-        shutdown();
+    // **** access current PCB exclusively
+    let mut process_inner = process.inner_exclusive_access();
+    // the stack of the main thread goes away with the address space
+    if task.tid != 0 {
+        process_inner
+            .memory_set
+            .munmap(task.ustack_top - USER_STACK_SIZE, USER_STACK_SIZE);
+    }
+    // the process keeps the time of its exited threads
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.charge_time(false);
+    process_inner.utime_us += core::mem::take(&mut task_inner.utime_us);
+    process_inner.stime_us += core::mem::take(&mut task_inner.stime_us);
+    drop(task_inner);
+    process_inner.alive_threads -= 1;
+    let last_thread = process_inner.alive_threads == 0;
+    drop(process_inner);
+    // **** release current PCB
+    if last_thread {
+        exit_process(&process);
     }
 
-    // remove from pid2task
-    remove_from_pid2task(task.getpid());
     // **** access current TCB exclusively
-    let mut inner = task.inner_exclusive_access();
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.exit_code = Some(exit_code);
     // Change status to Zombie
-    inner.task_status = TaskStatus::Zombie;
-    // Record exit code
+    task_inner.task_status = TaskStatus::Zombie;
+    drop(task_inner);
+    // **** release current TCB
+    // waittid checks the threads with the lock held, like waitpid
+    drop(process.inner_exclusive_access());
+    process.thread_exit.wake_all();
+
+    drop(process);
+    drop(task);
+    // we do not have to save task context
+    let mut _unused = KContext::blank();
+    schedule(&mut _unused as *mut _);
+}
+
+/// Exit the whole process of the current thread, e.g. when it is killed.
+pub fn exit_current_process_and_run_next(exit_code: i32) {
+    start_process_exit(&current_process(), exit_code);
+    exit_current_and_run_next(exit_code);
+}
+
+/// Tell every thread of `process` to exit. The first exit code is kept.
+fn start_process_exit(process: &ProcessControlBlock, exit_code: i32) {
+    let mut inner = process.inner_exclusive_access();
+    if inner.exiting {
+        return;
+    }
+    inner.exiting = true;
     inner.exit_code = exit_code;
-    // do not move to its parent but under initproc
+    let tasks: Vec<_> = inner.tasks.iter().flatten().cloned().collect();
+    drop(inner);
+    // the threads exit on their way back to user mode
+    for task in tasks {
+        wakeup_task(task);
+    }
+}
 
-    // ++++++ access initproc TCB exclusively
-    {
-        let mut initproc_inner = INITPROC.inner_exclusive_access();
-        for child in inner.children.iter() {
-            child.inner_exclusive_access().parent = Some(Arc::downgrade(&INITPROC));
-            initproc_inner.children.push(child.clone());
-        }
+/// Charge the time since the current thread entered user mode as user time,
+/// when it traps into the kernel.
+pub fn current_charge_user_time() {
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().charge_time(true);
+}
+
+/// Send SIGVTALRM or SIGPROF to the current process once its user time, or
+/// its user and system time, reaches ITIMER_VIRTUAL or ITIMER_PROF.
+pub fn current_check_cpu_timers() {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    if !inner.itimers[ITIMER_VIRTUAL].armed() && !inner.itimers[ITIMER_PROF].armed() {
+        return;
+    }
+    let (utime, stime) = inner.times();
+    let mut signals = Vec::new();
+    if inner.itimers[ITIMER_VIRTUAL].expire(utime) {
+        signals.push(SignalFlags::SIGVTALRM);
+    }
+    if inner.itimers[ITIMER_PROF].expire(utime + stime) {
+        signals.push(SignalFlags::SIGPROF);
     }
-    // ++++++ release parent PCB
+    drop(inner);
+    for signal in signals {
+        send_signal(&process, SigInfo::kernel(signal));
+    }
+}
+
+/// Send SIGALRM to `process` if its ITIMER_REAL has expired, and wait for
+/// the next interval.
+pub fn real_timer_expired(process: &Arc<ProcessControlBlock>) {
+    let mut inner = process.inner_exclusive_access();
+    if inner.is_zombie {
+        return;
+    }
+    let timer = &mut inner.itimers[ITIMER_REAL];
+    // it has been set again meanwhile
+    if !timer.expire(get_time_us()) {
+        return;
+    }
+    if timer.armed() {
+        add_real_timer(timer.expire_us, process);
+    }
+    drop(inner);
+    send_signal(process, SigInfo::kernel(SignalFlags::SIGALRM));
+}
 
-    inner.children.clear();
+/// Whether the current thread has used up its time slice.
+pub fn current_slice_expired() -> bool {
+    let task = current_task().unwrap();
+    let task_inner = task.inner_exclusive_access();
+    get_time_us() - task_inner.slice_start_us >= task_inner.sched.time_slice_us()
+}
+
+/// Whether another thread has made the current process exit.
+pub fn current_process_exiting() -> bool {
+    current_process().inner_exclusive_access().exiting
+}
+
+/// Release the resources of `process` after its last thread has exited.
+fn exit_process(process: &Arc<ProcessControlBlock>) {
+    let pid = process.getpid();
+    if pid == IDLE_PID {
+        println!(
+            "[kernel] Idle process exit with exit_code {} ...",
+            process.inner_exclusive_access().exit_code
+        );
+        shutdown();
+    }
+
+    // remove from pid2process
+    remove_from_pid2process(pid);
+    remove_real_timer(process);
+    // **** access current PCB exclusively
+    let mut inner = process.inner_exclusive_access();
+    let children = core::mem::take(&mut inner.children);
     // deallocate user space
     inner.memory_set.recycle_data_pages();
-    // drop file descriptors
-    inner.fd_table.clear();
+    // drop file descriptors later, closing a pipe wakes up other tasks
+    let fd_table = core::mem::take(&mut inner.fd_table);
+    // the threads refer to the process, break the cycle
+    let tasks = core::mem::take(&mut inner.tasks);
+    let parent = inner.parent.as_ref().and_then(|parent| parent.upgrade());
+    let info = exit_info(pid, &inner);
+    // Change status to Zombie
+    inner.is_zombie = true;
     drop(inner);
     // **** release current PCB
-    // drop task manually to maintain rc correctly
-    drop(task);
-    // we do not have to save task context
-    let mut _unused = KContext::blank();
-    schedule(&mut _unused as *mut _);
+    drop(fd_table);
+    drop(tasks);
+
+    // do not move to its parent but under initproc
+    let mut zombie = None;
+    for child in children.iter() {
+        let mut child_inner = child.inner_exclusive_access();
+        child_inner.parent = Some(Arc::downgrade(&INITPROC));
+        if child_inner.is_zombie {
+            zombie = Some(exit_info(child.getpid(), &child_inner));
+        }
+    }
+    // ++++++ access initproc PCB exclusively
+    INITPROC.inner_exclusive_access().children.extend(children);
+    // ++++++ release initproc PCB
+    if let Some(info) = zombie {
+        notify_parent(&INITPROC, info);
+    }
+    if let Some(parent) = parent {
+        // ---- access parent PCB exclusively
+        let mut parent_inner = parent.inner_exclusive_access();
+        // a parent ignoring SIGCHLD doesn't wait for its children, which
+        // are released at once
+        if parent_inner.signal_actions.table[SignalFlags::SIGCHLD.signum()].handler == SIG_IGN {
+            parent_inner
+                .children
+                .retain(|child| !Arc::ptr_eq(child, process));
+        }
+        drop(parent_inner);
+        // ---- release parent PCB
+        notify_parent(&parent, info);
+    }
+}
+
+/// SIGCHLD about process `pid`, which has exited or been killed.
+fn exit_info(pid: usize, inner: &ProcessControlBlockInner) -> SigInfo {
+    match inner.killed {
+        Some(sig) => SigInfo::child(pid, CLD_KILLED, sig as i32),
+        None => SigInfo::child(pid, CLD_EXITED, inner.exit_code),
+    }
+}
+
+/// Tell `parent` that one of its children has exited, stopped or continued:
+/// send it SIGCHLD with `info` and wake up its tasks in waitpid.
+fn notify_parent(parent: &ProcessControlBlock, info: SigInfo) {
+    send_signal(parent, info);
+    // waitpid checks the children with the lock held, so it has either seen
+    // the change or joined the queue once we get the lock
+    drop(parent.inner_exclusive_access());
+    parent.child_exit.wake_all();
 }
 
 lazy_static! {
-    pub static ref INITPROC: Arc<TaskControlBlock> = Arc::new({
+    pub static ref INITPROC: Arc<ProcessControlBlock> = {
         let inode = open_file("user_shell", OpenFlags::RDONLY).unwrap();
         let v = inode.read_all();
-        TaskControlBlock::new(v.as_slice())
-    });
+        ProcessControlBlock::new(v.as_slice())
+    };
 }
 
 pub fn add_initproc() {
     trace!("os::task::add_initproc");
-    add_task(INITPROC.clone());
+    let task = INITPROC.inner_exclusive_access().get_task(0).unwrap();
+    add_task(task);
 }
 
+/// The exit code and the reason if a signal has killed the current process.
 pub fn check_signals_error_of_current() -> Option<(i32, &'static str)> {
     trace!("os::task::check_signals_error_of_current");
+    let process = current_process();
+    let sig = process.inner_exclusive_access().killed?;
+    let signal = SignalFlags::from_bits(1 << sig).unwrap();
+    Some((-(sig as i32), signal.description()))
+}
+
+/// Whether the current thread has a pending signal which is not masked,
+/// which interrupts a blocking syscall.
+pub fn current_signal_pending() -> bool {
     let task = current_task().unwrap();
     let task_inner = task.inner_exclusive_access();
-    // println!(
-    //     "[K] check_signals_error_of_current {:?}",
-    //     task_inner.signals
-    // );
-    task_inner.signals.check_error()
+    let signals = task_inner.pending.signals;
+    let signal_mask = task_inner.signal_mask;
+    drop(task_inner);
+    let signals = signals | task.process.inner_exclusive_access().pending.signals;
+    !(signals - signal_mask).is_empty()
+}
+
+/// Whether the current thread must give up a wait which signals don't
+/// interrupt: its process is exiting or has been sent SIGKILL.
+pub fn current_fatal_pending() -> bool {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    inner.exiting || inner.killed.is_some() || inner.pending.contains(SignalFlags::SIGKILL)
+}
+
+/// Make the signal of `info` pending on `process` and wake up its threads to
+/// handle it, unless the process ignores it. A standard signal which is
+/// pending already is merged with it. Return false if the signal is
+/// real-time and can't be queued.
+pub fn send_signal(process: &ProcessControlBlock, info: SigInfo) -> bool {
+    let signal = info.signal();
+    // the null signal only checks that the process exists
+    if signal == SignalFlags::SIGDEF {
+        return true;
+    }
+    // even if it is blocked or ignored
+    if signal == SignalFlags::SIGCONT {
+        continue_process(process);
+    }
+    let mut inner = process.inner_exclusive_access();
+    if signal.default_action() == DefaultAction::Stop {
+        inner.pending.discard(SignalFlags::SIGCONT);
+    }
+    if inner.signal_actions.ignores(signal) {
+        return true;
+    }
+    if !inner.pending.push(info) {
+        return false;
+    }
+    let tasks: Vec<_> = inner.tasks.iter().flatten().cloned().collect();
+    drop(inner);
+    // let the threads blocked in the kernel notice the signal
+    for task in tasks {
+        wakeup_task(task);
+    }
+    true
+}
+
+/// Continue `process` if it is stopped, and discard its pending stops.
+fn continue_process(process: &ProcessControlBlock) {
+    let mut inner = process.inner_exclusive_access();
+    inner.pending.discard(
+        SignalFlags::SIGSTOP | SignalFlags::SIGTSTP | SignalFlags::SIGTTIN | SignalFlags::SIGTTOU,
+    );
+    if !inner.frozen {
+        return;
+    }
+    inner.frozen = false;
+    inner.stop_status = None;
+    // the parent learns it from waitpid with WCONTINUED
+    inner.continued = true;
+    let parent = inner.parent.as_ref().and_then(|p| p.upgrade());
+    let tasks: Vec<_> = inner.tasks.iter().flatten().cloned().collect();
+    drop(inner);
+    // the threads are blocked in handle_signals
+    for task in tasks {
+        wakeup_task(task);
+    }
+    if let Some(parent) = parent {
+        let info = SigInfo::child(
+            process.getpid(),
+            CLD_CONTINUED,
+            SignalFlags::SIGCONT.signum() as i32,
+        );
+        notify_parent(&parent, info);
+    }
+}
+
+/// The processes in process group `pgid`.
+pub fn processes_in_group(pgid: usize) -> Vec<Arc<ProcessControlBlock>> {
+    all_processes()
+        .into_iter()
+        .filter(|process| process.inner_exclusive_access().pgid == pgid)
+        .collect()
+}
+
+/// Send the signal of `info` to every process in group `pgid`, return how
+/// many there are.
+pub fn send_group_signal(pgid: usize, info: SigInfo) -> usize {
+    let processes = processes_in_group(pgid);
+    for process in processes.iter() {
+        send_signal(process, info);
+    }
+    processes.len()
 }
 
-pub fn current_add_signal(signal: SignalFlags) {
+/// Raise the signal of `info` for a fault of the current thread. The
+/// faulting instruction would run again, so the signal can't be blocked or
+/// ignored, and kills the process if it is blocked, e.g. in its own handler.
+pub fn current_add_signal(info: SigInfo) {
     trace!("os::task::current_add_signal");
+    let signal = info.signal();
     let task = current_task().unwrap();
+    let mut process_inner = task.process.inner_exclusive_access();
     let mut task_inner = task.inner_exclusive_access();
-    task_inner.signals |= signal;
-    // println!(
-    //     "[K] current_add_signal:: current task sigflag {:?}",
-    //     task_inner.signals
-    // );
+    let action = &mut process_inner.signal_actions.table[signal.signum()];
+    if action.handler == SIG_IGN || task_inner.signal_mask.contains(signal) {
+        action.handler = SIG_DFL;
+    }
+    task_inner.signal_mask.remove(signal);
+    // only the faulting thread can handle it
+    task_inner.pending.push(info);
+}
+
+/// SIGSEGV for an access to `vaddr` of the current process which requires
+/// `perm` and is illegal.
+pub fn current_segv_info(vaddr: usize, perm: MapPermission) -> SigInfo {
+    let process = current_process();
+    let mapped = process
+        .inner_exclusive_access()
+        .memory_set
+        .contains(vaddr.into());
+    let code = if mapped { SEGV_ACCERR } else { SEGV_MAPERR };
+    let mut info = SigInfo::fault(SignalFlags::SIGSEGV, code, vaddr);
+    // the PROT_* bits of the access
+    info.fields[1] = (perm.bits() >> 1) as usize;
+    info
 }
 
-fn call_kernel_signal_handler(signal: SignalFlags) {
+/// Try to resolve a page fault of the current task which requires `perm`.
+pub fn current_handle_page_fault(vaddr: usize, perm: MapPermission) -> bool {
+    trace!("os::task::current_handle_page_fault");
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    process_inner
+        .memory_set
+        .handle_page_fault(vaddr.into(), perm)
+}
+
+/// Take the default action of `signal`, which the current process doesn't
+/// catch.
+fn call_kernel_signal_handler(sig: usize, signal: SignalFlags) {
     trace!("os::task::call_kernel_signal_handler");
-    let task = current_task().unwrap();
-    let mut task_inner = task.inner_exclusive_access();
-    match signal {
-        SignalFlags::SIGSTOP => {
-            task_inner.frozen = true;
-            task_inner.signals ^= SignalFlags::SIGSTOP;
-        }
-        SignalFlags::SIGCONT => {
-            if task_inner.signals.contains(SignalFlags::SIGCONT) {
-                task_inner.signals ^= SignalFlags::SIGCONT;
-                task_inner.frozen = false;
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    match signal.default_action() {
+        DefaultAction::Stop => {
+            if !process_inner.frozen {
+                process_inner.frozen = true;
+                // the parent learns it from waitpid with WUNTRACED
+                process_inner.stop_status = Some(0x7f | (sig as i32) << 8);
+                let parent = process_inner.parent.as_ref().and_then(|p| p.upgrade());
+                drop(process_inner);
+                if let Some(parent) = parent {
+                    let info = SigInfo::child(process.getpid(), CLD_STOPPED, sig as i32);
+                    notify_parent(&parent, info);
+                }
             }
         }
-        _ => {
-            // println!(
-            //     "[K] call_kernel_signal_handler:: current task sigflag {:?}",
-            //     task_inner.signals
-            // );
-            task_inner.killed = true;
+        // SIGCONT has continued the process when it was sent
+        DefaultAction::Ignore | DefaultAction::Continue => {}
+        DefaultAction::Terminate | DefaultAction::CoreDump => {
+            // the first signal is the reason of the exit
+            process_inner.killed.get_or_insert(sig);
         }
     }
 }
 
-fn call_user_signal_handler(sig: usize, signal: SignalFlags) {
+/// Run the handler of `signal` when the current thread returns to user mode,
+/// on a frame pushed on the user stack with `info`. `interrupted` is where a
+/// syscall interrupted by the signal returns, with EINTR unless the handler
+/// has SA_RESTART.
+fn call_user_signal_handler(
+    sig: usize,
+    signal: SignalFlags,
+    info: SigInfo,
+    interrupted: Option<usize>,
+) {
     trace!("os::task::call_user_signal_handler");
     let task = current_task().unwrap();
+    let mut process_inner = task.process.inner_exclusive_access();
+    let action = process_inner.signal_actions.table[sig];
     let mut task_inner = task.inner_exclusive_access();
+    let trap_cx = task_inner.get_trap_cx();
+    if let Some(next_pc) = interrupted {
+        if !action.flags.contains(SignalActionFlags::SA_RESTART) {
+            trap_cx[TrapFrameArgs::SEPC] = next_pc;
+            trap_cx[TrapFrameArgs::RET] = -(Errno::EINTR as isize) as usize;
+        }
+    }
 
-    let handler = task_inner.signal_actions.table[sig].handler;
-    if handler != 0 {
-        // user handler
-
-        // handle flag
-        task_inner.handling_sig = sig as isize;
-        task_inner.signals ^= signal;
+    // push the frame, on the alternate stack unless the thread runs on it
+    // already. The mask of the frame is the one before sigsuspend
+    let signal_mask = task_inner
+        .saved_mask
+        .take()
+        .unwrap_or(task_inner.signal_mask);
+    let sp = trap_cx[TrapFrameArgs::SP];
+    let stack = task_inner.signal_stack;
+    let top = if action.flags.contains(SignalActionFlags::SA_ONSTACK)
+        && stack.enabled()
+        && !stack.contains(sp)
+    {
+        stack.sp + stack.size
+    } else {
+        sp
+    };
+    let frame = (top - size_of::<SignalFrame>()) & !0xf;
+    let signal_frame = SignalFrame::new(info, trap_cx, signal_mask, stack.status(sp));
+    let frame_ptr = UserPtr::new(frame as *const SignalFrame);
+    if frame_ptr
+        .write(&mut process_inner.memory_set, signal_frame)
+        .is_err()
+    {
+        // the stack has overflowed
+        process_inner
+            .killed
+            .get_or_insert(SignalFlags::SIGSEGV.signum());
+        return;
+    }
+    task_inner.drop_left_frames(sp);
+    task_inner.signal_frames.push(frame);
 
-        // backup trapframe
-        let trap_ctx = task_inner.get_trap_cx();
-        task_inner.trap_ctx_backup = Some(trap_ctx.clone());
+    // block the signal and the mask of the action until the handler returns
+    let mut blocked = action.mask;
+    if !action.flags.contains(SignalActionFlags::SA_NODEFER) {
+        blocked |= signal;
+    }
+    task_inner.signal_mask |= blocked - SignalFlags::unblockable();
 
-        // modify trapframe
-        trap_ctx[TrapFrameArgs::SEPC] = handler;
+    // the handler returns to the trampoline, which makes it sigreturn
+    trap_cx[TrapFrameArgs::SEPC] = action.handler;
+    trap_cx[TrapFrameArgs::SP] = frame;
+    trap_cx[TrapFrameArgs::RA] =
+        if action.flags.contains(SignalActionFlags::SA_RESTORER) && action.restorer != 0 {
+            action.restorer
+        } else {
+            SIGRETURN_TRAMPOLINE
+        };
+    trap_cx[TrapFrameArgs::ARG0] = sig;
+    if action.flags.contains(SignalActionFlags::SA_SIGINFO) {
+        trap_cx[TrapFrameArgs::ARG1] = frame + offset_of!(SignalFrame, info);
+        trap_cx[TrapFrameArgs::ARG2] = frame + offset_of!(SignalFrame, ucontext);
+    }
+}
 
-        // put args (a0)
-        trap_ctx[TrapFrameArgs::ARG0] = sig;
-    } else {
-        info!("task id: {}", task.getpid());
-        info!("{:#x?}", task_inner.get_trap_cx());
-        // default action
-        println!("[K] task/call_user_signal_handler: default action: ignore it or kill process");
+/// Return from the innermost handler of the current thread to the context
+/// in its frame, which the handler may have changed, with the signal mask in
+/// the frame. Return false if no handler is running.
+pub fn current_sigreturn() -> bool {
+    trace!("os::task::current_sigreturn");
+    let task = current_task().unwrap();
+    let mut process_inner = task.process.inner_exclusive_access();
+    let mut task_inner = task.inner_exclusive_access();
+    let trap_cx = task_inner.get_trap_cx();
+    // the handler runs on top of its frame, and returns with the stack
+    // pointer at it
+    task_inner.drop_left_frames(trap_cx[TrapFrameArgs::SP]);
+    let Some(frame) = task_inner.signal_frames.pop() else {
+        return false;
+    };
+    let ucontext_ptr = UserPtr::new((frame + offset_of!(SignalFrame, ucontext)) as *const UContext);
+    match ucontext_ptr.read(&mut process_inner.memory_set) {
+        Ok(ucontext) => {
+            task_inner.signal_mask =
+                SignalFlags::from_bits_truncate(ucontext.sigmask) - SignalFlags::unblockable();
+            *trap_cx = ucontext.trap_cx(trap_cx);
+        }
+        Err(_) => {
+            process_inner
+                .killed
+                .get_or_insert(SignalFlags::SIGSEGV.signum());
+        }
     }
+    true
 }
 
-fn check_pending_signals() {
+fn check_pending_signals(interrupted: Option<usize>) {
     trace!("os::task::check_pending_signals");
     for sig in 0..(MAX_SIG + 1) {
         let task = current_task().unwrap();
-        let task_inner = task.inner_exclusive_access();
+        let mut process_inner = task.process.inner_exclusive_access();
+        let mut task_inner = task.inner_exclusive_access();
         let signal = SignalFlags::from_bits(1 << sig).unwrap();
-        if task_inner.signals.contains(signal) && (!task_inner.signal_mask.contains(signal)) {
-            let mut masked = true;
-            let handling_sig = task_inner.handling_sig;
-            if handling_sig == -1 {
-                masked = false;
-            } else {
-                let handling_sig = handling_sig as usize;
-                if !task_inner.signal_actions.table[handling_sig]
-                    .mask
-                    .contains(signal)
-                {
-                    masked = false;
-                }
-            }
-            if !masked {
-                drop(task_inner);
-                drop(task);
-                if signal == SignalFlags::SIGKILL
-                    || signal == SignalFlags::SIGSTOP
-                    || signal == SignalFlags::SIGCONT
-                    || signal == SignalFlags::SIGDEF
-                {
-                    // signal is a kernel signal
-                    call_kernel_signal_handler(signal);
-                } else {
-                    // signal is a user signal
-                    call_user_signal_handler(sig, signal);
-                    return;
-                }
-            }
+        if !process_inner.pending.contains(signal) && !task_inner.pending.contains(signal) {
+            continue;
+        }
+        // it became ignored after it was sent
+        if process_inner.signal_actions.ignores(signal) {
+            process_inner.pending.discard(signal);
+            task_inner.pending.discard(signal);
+            continue;
+        }
+        // a blocked signal stays pending
+        if task_inner.signal_mask.contains(signal) {
+            continue;
+        }
+        // those of the thread first, and one at a time if they are queued
+        let info = task_inner
+            .pending
+            .pop(signal)
+            .or_else(|| process_inner.pending.pop(signal))
+            .unwrap();
+        let handler = process_inner.signal_actions.table[sig].handler;
+        drop(task_inner);
+        drop(process_inner);
+        drop(task);
+        if handler == SIG_DFL {
+            call_kernel_signal_handler(sig, signal);
+        } else {
+            call_user_signal_handler(sig, signal, info, interrupted);
+            return;
         }
     }
 }
 
-pub fn handle_signals() {
+/// Handle the pending signals before the current thread returns to user
+/// mode. `interrupted` is where a syscall interrupted by a signal returns
+/// if a handler runs, otherwise it runs again. A stopped process blocks here
+/// until it is continued or killed.
+pub fn handle_signals(interrupted: Option<usize>) {
     trace!("os::task::handle_signals");
     loop {
-        check_pending_signals();
+        check_pending_signals(interrupted);
         let (frozen, killed) = {
-            let task = current_task().unwrap();
-            let task_inner = task.inner_exclusive_access();
-            (task_inner.frozen, task_inner.killed)
+            let process = current_process();
+            let process_inner = process.inner_exclusive_access();
+            (
+                process_inner.frozen,
+                process_inner.killed.is_some() || process_inner.exiting,
+            )
         };
         if !frozen || killed {
             break;
         }
-        suspend_current_and_run_next();
+        // until SIGCONT or SIGKILL wakes it
+        block_current_and_run_next();
+    }
+    // sigsuspend has returned without running a handler
+    let task = current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    if let Some(signal_mask) = task_inner.saved_mask.take() {
+        task_inner.signal_mask = signal_mask;
     }
 }
@@ -0,0 +1,81 @@
+use super::{SigInfo, SignalFlags, MAX_SIG};
+use alloc::collections::{BTreeMap, VecDeque};
+
+/// Most real-time signals queued at a time.
+pub const SIGQUEUE_MAX: usize = 256;
+
+/// Signals sent to a process or a thread and not handled yet. A standard
+/// signal is pending once however many times it is sent, a real-time one
+/// is queued each time with its `SigInfo`.
+pub struct SignalQueue {
+    pub signals: SignalFlags,
+    // the pending instances of each signal, oldest first
+    infos: BTreeMap<usize, VecDeque<SigInfo>>,
+    // number of real-time signals in the queue
+    queued: usize,
+}
+
+impl Default for SignalQueue {
+    fn default() -> Self {
+        Self {
+            signals: SignalFlags::empty(),
+            infos: BTreeMap::new(),
+            queued: 0,
+        }
+    }
+}
+
+impl SignalQueue {
+    pub fn contains(&self, signal: SignalFlags) -> bool {
+        self.signals.contains(signal)
+    }
+    /// Make the signal of `info` pending. Return false if it is real-time
+    /// and the queue is full.
+    pub fn push(&mut self, info: SigInfo) -> bool {
+        let signal = info.signal();
+        if signal.is_realtime() {
+            if self.queued == SIGQUEUE_MAX {
+                return false;
+            }
+            self.queued += 1;
+        } else if self.signals.contains(signal) {
+            // the first one is kept
+            return true;
+        }
+        self.infos
+            .entry(signal.signum())
+            .or_default()
+            .push_back(info);
+        self.signals.insert(signal);
+        true
+    }
+    /// Take the oldest pending instance of `signal`.
+    pub fn pop(&mut self, signal: SignalFlags) -> Option<SigInfo> {
+        let sig = signal.signum();
+        let infos = self.infos.get_mut(&sig)?;
+        let info = infos.pop_front()?;
+        if signal.is_realtime() {
+            self.queued -= 1;
+        }
+        if infos.is_empty() {
+            self.infos.remove(&sig);
+            self.signals.remove(signal);
+        }
+        Some(info)
+    }
+    /// Discard every pending instance of `signals`.
+    pub fn discard(&mut self, signals: SignalFlags) {
+        for sig in 0..=MAX_SIG {
+            let signal = SignalFlags::from_bits(1 << sig).unwrap();
+            if !signals.contains(signal) {
+                continue;
+            }
+            if let Some(infos) = self.infos.remove(&sig) {
+                if signal.is_realtime() {
+                    self.queued -= infos.len();
+                }
+            }
+        }
+        self.signals.remove(signals);
+    }
+}
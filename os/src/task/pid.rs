@@ -1,6 +1,6 @@
-use crate::sync::UPSafeCell;
 use alloc::vec::Vec;
 use lazy_static::*;
+use polyhal::utils::MutexNoIrq;
 
 struct PidAllocator {
     current: usize,
@@ -34,8 +34,7 @@ impl PidAllocator {
 }
 
 lazy_static! {
-    static ref PID_ALLOCATOR: UPSafeCell<PidAllocator> =
-        unsafe { UPSafeCell::new(PidAllocator::new()) };
+    static ref PID_ALLOCATOR: MutexNoIrq<PidAllocator> = MutexNoIrq::new(PidAllocator::new());
 }
 
 pub struct PidHandle(pub usize);
@@ -43,10 +42,10 @@ pub struct PidHandle(pub usize);
 impl Drop for PidHandle {
     fn drop(&mut self) {
         //println!("drop pid {}", self.0);
-        PID_ALLOCATOR.exclusive_access().dealloc(self.0);
+        PID_ALLOCATOR.lock().dealloc(self.0);
     }
 }
 
 pub fn pid_alloc() -> PidHandle {
-    PID_ALLOCATOR.exclusive_access().alloc()
+    PID_ALLOCATOR.lock().alloc()
 }
@@ -0,0 +1,337 @@
+use super::manager::insert_into_pid2process;
+use super::scheduler::SchedEntity;
+use super::{
+    pid_alloc, PidHandle, SignalAction, SignalActions, SignalFlags, SignalQueue, SignalStack,
+    TaskControlBlock, SIG_IGN,
+};
+use crate::fs::{File, Stdin, Stdout};
+use crate::mm::{MemorySet, UserPtr, UserSlice};
+use crate::sync::{Banker, Condvar, Mutex, Semaphore, WaitQueue};
+use crate::timer::{get_time_us, IntervalTimer, ITIMER_REAL, ITIMER_VIRTUAL};
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::DerefMut;
+use log::*;
+use polyhal::pagetable::PageTable;
+use polyhal::utils::MutexNoIrq;
+use polyhal_trap::trapframe::{TrapFrame, TrapFrameArgs};
+
+pub struct ProcessControlBlock {
+    // immutable
+    pub pid: PidHandle,
+    // tasks blocked in waitpid until one of the children exits
+    pub child_exit: WaitQueue,
+    // threads blocked in waittid until another thread exits
+    pub thread_exit: WaitQueue,
+    // mutable
+    inner: MutexNoIrq<ProcessControlBlockInner>,
+}
+
+pub struct ProcessControlBlockInner {
+    pub is_zombie: bool,
+    // the process is exiting, its threads exit before returning to user mode
+    pub exiting: bool,
+    pub memory_set: MemorySet,
+    pub parent: Option<Weak<ProcessControlBlock>>,
+    pub children: Vec<Arc<ProcessControlBlock>>,
+    pub exit_code: i32,
+    pub fd_table: Vec<Option<Arc<dyn File + Send + Sync>>>,
+    // signals sent to the process, which any of its threads handles
+    pub pending: SignalQueue,
+    // Signal actions
+    pub signal_actions: SignalActions,
+    // the signal which has killed the process
+    pub killed: Option<usize>,
+    // if the process is frozen by a signal
+    pub frozen: bool,
+    // status of the last stop for waitpid with WUNTRACED, until reported
+    pub stop_status: Option<i32>,
+    // continued since it stopped, for waitpid with WCONTINUED, until reported
+    pub continued: bool,
+    // process group and session
+    pub pgid: usize,
+    pub sid: usize,
+    // threads indexed by tid, an exited thread stays until it is waited
+    pub tasks: Vec<Option<Arc<TaskControlBlock>>>,
+    // number of threads which have not exited yet
+    pub alive_threads: usize,
+    // synchronization objects of the threads, indexed by their ids
+    pub mutex_list: Vec<Option<Arc<Mutex>>>,
+    pub semaphore_list: Vec<Option<Arc<Semaphore>>>,
+    pub condvar_list: Vec<Option<Arc<Condvar>>>,
+    // fail a lock or down which may deadlock, instead of blocking
+    pub deadlock_detect: bool,
+    // who holds and waits for the mutexes and semaphores
+    pub mutex_banker: Banker,
+    pub semaphore_banker: Banker,
+    // user and system time of the exited threads in microseconds
+    pub utime_us: usize,
+    pub stime_us: usize,
+    // user and system time of the children which have been waited for
+    pub cutime_us: usize,
+    pub cstime_us: usize,
+    // ITIMER_REAL, ITIMER_VIRTUAL and ITIMER_PROF
+    pub itimers: [IntervalTimer; 3],
+}
+
+impl ProcessControlBlockInner {
+    pub fn get_user_token(&self) -> PageTable {
+        self.memory_set.token()
+    }
+    pub fn get_file(&self, fd: usize) -> Option<Arc<dyn File + Send + Sync>> {
+        self.fd_table.get(fd).cloned().flatten()
+    }
+    pub fn alloc_fd(&mut self) -> usize {
+        if let Some(fd) = (0..self.fd_table.len()).find(|fd| self.fd_table[*fd].is_none()) {
+            fd
+        } else {
+            self.fd_table.push(None);
+            self.fd_table.len() - 1
+        }
+    }
+    pub fn alloc_tid(&mut self) -> usize {
+        if let Some(tid) = (0..self.tasks.len()).find(|tid| self.tasks[*tid].is_none()) {
+            tid
+        } else {
+            self.tasks.push(None);
+            self.tasks.len() - 1
+        }
+    }
+    pub fn get_task(&self, tid: usize) -> Option<Arc<TaskControlBlock>> {
+        self.tasks.get(tid).cloned().flatten()
+    }
+    /// User and system time of all the threads, running or not.
+    pub fn times(&self) -> (usize, usize) {
+        self.tasks
+            .iter()
+            .flatten()
+            .fold((self.utime_us, self.stime_us), |(utime, stime), task| {
+                let task_inner = task.inner_exclusive_access();
+                (utime + task_inner.utime_us, stime + task_inner.stime_us)
+            })
+    }
+    /// The clock of the interval timer `which`: the real time, the user time
+    /// or the user and system time.
+    pub fn itimer_now(&self, which: usize) -> usize {
+        match which {
+            ITIMER_REAL => get_time_us(),
+            ITIMER_VIRTUAL => self.times().0,
+            _ => {
+                let (utime, stime) = self.times();
+                utime + stime
+            }
+        }
+    }
+    /// Add `task` as a new thread, which must take the tid from `alloc_tid`.
+    pub fn add_thread(&mut self, task: Arc<TaskControlBlock>) {
+        let tid = task.tid;
+        self.tasks[tid] = Some(task);
+        self.alive_threads += 1;
+    }
+}
+
+impl ProcessControlBlock {
+    pub fn inner_exclusive_access(&self) -> impl DerefMut<Target = ProcessControlBlockInner> + '_ {
+        self.inner.lock()
+    }
+    fn new_with(
+        memory_set: MemorySet,
+        parent: Option<Weak<ProcessControlBlock>>,
+        fd_table: Vec<Option<Arc<dyn File + Send + Sync>>>,
+        signal_actions: SignalActions,
+        // (pgid, sid) to join, or a new session led by the process
+        group: Option<(usize, usize)>,
+    ) -> Arc<Self> {
+        let pid = pid_alloc();
+        let (pgid, sid) = group.unwrap_or((pid.0, pid.0));
+        let process = Arc::new(Self {
+            pid,
+            child_exit: WaitQueue::new(),
+            thread_exit: WaitQueue::new(),
+            inner: MutexNoIrq::new(ProcessControlBlockInner {
+                is_zombie: false,
+                exiting: false,
+                memory_set,
+                parent,
+                children: Vec::new(),
+                exit_code: 0,
+                fd_table,
+                pending: SignalQueue::default(),
+                signal_actions,
+                killed: None,
+                frozen: false,
+                stop_status: None,
+                continued: false,
+                pgid,
+                sid,
+                tasks: Vec::new(),
+                alive_threads: 0,
+                mutex_list: Vec::new(),
+                semaphore_list: Vec::new(),
+                condvar_list: Vec::new(),
+                deadlock_detect: false,
+                mutex_banker: Banker::default(),
+                semaphore_banker: Banker::default(),
+                utime_us: 0,
+                stime_us: 0,
+                cutime_us: 0,
+                cstime_us: 0,
+                itimers: [IntervalTimer::default(); 3],
+            }),
+        });
+        insert_into_pid2process(process.getpid(), process.clone());
+        process
+    }
+    /// Create a process running `elf_data` in its main thread, which has
+    /// not been added to the scheduler yet.
+    pub fn new(elf_data: &[u8]) -> Arc<Self> {
+        trace!("os::task::ProcessControlBlock::new");
+        // memory_set with elf program headers/trampoline/trap context/user stack
+        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        let fd_table: Vec<Option<Arc<dyn File + Send + Sync>>> = vec![
+            // 0 -> stdin
+            Some(Arc::new(Stdin)),
+            // 1 -> stdout
+            Some(Arc::new(Stdout)),
+            // 2 -> stderr
+            Some(Arc::new(Stdout)),
+        ];
+        let process = Self::new_with(memory_set, None, fd_table, SignalActions::default(), None);
+        let tid = process.inner_exclusive_access().alloc_tid();
+        let task = Arc::new(TaskControlBlock::new(
+            process.clone(),
+            tid,
+            user_sp,
+            SchedEntity::new(),
+            SignalFlags::empty(),
+        ));
+        // prepare TrapContext in user space
+        let trap_cx = task.inner_exclusive_access().get_trap_cx();
+        trap_cx[TrapFrameArgs::SEPC] = entry_point;
+        process.inner_exclusive_access().add_thread(task);
+        process
+    }
+    /// Replace the program of the process. `task` must be the main thread
+    /// and the only one which has not exited.
+    pub fn exec(&self, task: &TaskControlBlock, elf_data: &[u8], args: Vec<String>) {
+        trace!("os::task::ProcessControlBlock::exec");
+        // memory_set with elf program headers/trampoline/trap context/user stack
+        let (mut memory_set, mut user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        memory_set.activate();
+        // push arguments on user stack
+        user_sp -= (args.len() + 1) * core::mem::size_of::<usize>();
+        let argv_base = user_sp;
+        let argv = UserPtr::new(argv_base as *const usize);
+        argv.add(args.len())
+            .write(&mut memory_set, 0)
+            .expect("can't push arguments");
+        for i in 0..args.len() {
+            user_sp -= args[i].len() + 1;
+            argv.add(i)
+                .write(&mut memory_set, user_sp)
+                .expect("can't push arguments");
+            let mut arg = args[i].as_bytes().to_vec();
+            arg.push(0);
+            UserSlice::new(user_sp as *const u8, arg.len())
+                .copy_from_kernel(&mut memory_set, &arg)
+                .expect("can't push arguments");
+        }
+        // make the user_sp aligned to 8B for k210 platform
+        user_sp -= user_sp % core::mem::size_of::<usize>();
+        memory_set.activate();
+
+        // **** access current PCB exclusively
+        let mut inner = self.inner_exclusive_access();
+        // substitute memory_set
+        inner.memory_set = memory_set;
+        // the exited threads are gone with the old program
+        inner.tasks.truncate(1);
+        // and so are the synchronization objects
+        inner.mutex_list.clear();
+        inner.semaphore_list.clear();
+        inner.condvar_list.clear();
+        inner.deadlock_detect = false;
+        inner.mutex_banker = Banker::default();
+        inner.semaphore_banker = Banker::default();
+        // the handlers are gone with the old program, ignored signals stay
+        // ignored
+        for action in inner.signal_actions.table.iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SignalAction::default();
+            }
+        }
+        drop(inner);
+        // **** release current PCB
+        // initialize trap_cx
+        let mut trap_cx = TrapFrame::new();
+        trap_cx[TrapFrameArgs::SEPC] = entry_point;
+        trap_cx[TrapFrameArgs::SP] = user_sp;
+        trap_cx[TrapFrameArgs::ARG0] = args.len();
+        trap_cx[TrapFrameArgs::ARG1] = argv_base;
+        let mut task_inner = task.inner_exclusive_access();
+        task_inner.trap_cx = trap_cx;
+        task_inner.signal_stack = SignalStack::disabled();
+        task_inner.signal_frames.clear();
+    }
+    /// Copy the process with `task` as the main thread of the child, which
+    /// has not been added to the scheduler yet.
+    pub fn fork(self: &Arc<Self>, task: &TaskControlBlock) -> Arc<Self> {
+        trace!("os::task::ProcessControlBlock::fork");
+        // ---- hold parent PCB lock
+        let mut parent_inner = self.inner_exclusive_access();
+        // copy user space, including the stacks of all the threads
+        let memory_set = MemorySet::from_existed_user(&parent_inner.memory_set);
+        // copy fd table
+        let mut new_fd_table: Vec<Option<Arc<dyn File + Send + Sync>>> = Vec::new();
+        for fd in parent_inner.fd_table.iter() {
+            if let Some(file) = fd {
+                new_fd_table.push(Some(file.clone()));
+            } else {
+                new_fd_table.push(None);
+            }
+        }
+        let child = Self::new_with(
+            memory_set,
+            Some(Arc::downgrade(self)),
+            new_fd_table,
+            // inherit the signal_action
+            parent_inner.signal_actions.clone(),
+            // and the process group
+            Some((parent_inner.pgid, parent_inner.sid)),
+        );
+        // add child
+        parent_inner.children.push(child.clone());
+        drop(parent_inner);
+        // ---- release parent PCB
+        let tid = child.inner_exclusive_access().alloc_tid();
+        let task_inner = task.inner_exclusive_access();
+        // inherit the policy and nice, and start from the parent's run time
+        let sched = task_inner.sched;
+        // inherit the signal_mask
+        let signal_mask = task_inner.signal_mask;
+        // the child returns to the same place, and from the same handlers
+        let trap_cx = task_inner.trap_cx.clone();
+        let signal_stack = task_inner.signal_stack;
+        let signal_frames = task_inner.signal_frames.clone();
+        drop(task_inner);
+        let child_task = Arc::new(TaskControlBlock::new(
+            child.clone(),
+            tid,
+            task.ustack_top,
+            sched,
+            signal_mask,
+        ));
+        let mut child_task_inner = child_task.inner_exclusive_access();
+        child_task_inner.trap_cx = trap_cx;
+        child_task_inner.signal_stack = signal_stack;
+        child_task_inner.signal_frames = signal_frames;
+        drop(child_task_inner);
+        child.inner_exclusive_access().add_thread(child_task);
+        child
+    }
+    pub fn getpid(&self) -> usize {
+        self.pid.0
+    }
+}
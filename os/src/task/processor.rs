@@ -1,14 +1,17 @@
-use core::cell::LazyCell;
-
-use super::TaskControlBlock;
+use super::{add_task, ProcessControlBlock, TaskControlBlock};
 use super::{fetch_task, TaskStatus};
-use crate::sync::UPSafeCell;
+use crate::config::MAX_HARTS;
+use crate::fs::poll_stdin;
+use crate::timer::{check_timer, get_time_us};
 use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use lazy_static::*;
 use lazyinit::LazyInit;
 use log::*;
-use polyhal::kcontext::{context_switch_pt, KContext};
-use polyhal::pagetable::PageTable;
+use polyhal::kcontext::{context_switch_pt, read_current_tp, KContext, KContextArgs};
+use polyhal::pagetable::{PageTable, TLB};
+use polyhal::utils::MutexNoIrq;
+
 pub struct Processor {
     current: Option<Arc<TaskControlBlock>>,
     idle_task_cx: KContext,
@@ -33,21 +36,97 @@ impl Processor {
 }
 
 lazy_static! {
-    pub static ref PROCESSOR: UPSafeCell<Processor> = unsafe { UPSafeCell::new(Processor::new()) };
+    static ref PROCESSORS: [MutexNoIrq<Processor>; MAX_HARTS] =
+        core::array::from_fn(|_| MutexNoIrq::new(Processor::new()));
+}
+
+/// The thread pointer of each hart, which tells the harts apart.
+static HART_TPS: [AtomicUsize; MAX_HARTS] = [const { AtomicUsize::new(0) }; MAX_HARTS];
+static HART_NUM: AtomicUsize = AtomicUsize::new(0);
+
+/// Register the calling hart so that it gets a `Processor` of its own.
+pub fn init_hart() {
+    let id = HART_NUM.fetch_add(1, Ordering::SeqCst);
+    assert!(id < MAX_HARTS, "too many harts");
+    HART_TPS[id].store(read_current_tp(), Ordering::SeqCst);
+}
+
+/// Index of the `Processor` which belongs to the calling hart.
+fn hart_id() -> usize {
+    let tp = read_current_tp();
+    HART_TPS[..HART_NUM.load(Ordering::SeqCst)]
+        .iter()
+        .position(|hart_tp| hart_tp.load(Ordering::Relaxed) == tp)
+        .expect("hart is not registered")
+}
+
+/// The address space each hart runs in user mode, or 0 while it is in the
+/// kernel, and whether its TLB may still hold mappings which have changed.
+static USER_SPACES: [AtomicUsize; MAX_HARTS] = [const { AtomicUsize::new(0) }; MAX_HARTS];
+static TLB_STALE: [AtomicBool; MAX_HARTS] = [const { AtomicBool::new(false) }; MAX_HARTS];
+
+/// Record that the calling hart returns to user mode in the address space
+/// `space`, and flush its TLB if a shootdown has been requested.
+pub fn enter_user(space: usize) {
+    let id = hart_id();
+    USER_SPACES[id].store(space, Ordering::SeqCst);
+    if TLB_STALE[id].swap(false, Ordering::SeqCst) {
+        TLB::flush_all();
+    }
+}
+
+/// Record that the calling hart has trapped into the kernel.
+pub fn leave_user() {
+    USER_SPACES[hart_id()].store(0, Ordering::SeqCst);
+}
+
+/// Make every hart drop the TLB entries of the address space `space` after
+/// its page table has changed. The other harts flush before they return to
+/// user mode, and those in user mode in `space` now are waited for, until
+/// they trap into the kernel at the next timer interrupt at the latest.
+/// Only then the frames which have been unmapped can be freed.
+///
+/// The kernel reaches user memory through physical addresses, so the harts
+/// in the kernel don't use the stale entries meanwhile.
+pub fn shootdown_tlb(space: usize) {
+    TLB::flush_all();
+    let me = hart_id();
+    let harts = HART_NUM.load(Ordering::SeqCst);
+    for id in (0..harts).filter(|&id| id != me) {
+        TLB_STALE[id].store(true, Ordering::SeqCst);
+    }
+    for id in (0..harts).filter(|&id| id != me) {
+        // a hart which has cleared the flag has flushed after the change
+        while USER_SPACES[id].load(Ordering::SeqCst) == space
+            && TLB_STALE[id].load(Ordering::SeqCst)
+        {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+fn current_processor() -> &'static MutexNoIrq<Processor> {
+    &PROCESSORS[hart_id()]
 }
 
 pub fn run_tasks() {
     trace!("os::task::processor::run_tasks");
     loop {
-        let mut processor = PROCESSOR.exclusive_access();
+        let mut processor = current_processor().lock();
         if let Some(task) = fetch_task() {
             let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
+            let token = task.process.inner_exclusive_access().get_user_token();
             // access coming task TCB exclusively
             let mut task_inner = task.inner_exclusive_access();
+            // the task may have run on another hart last time
+            task_inner.task_cx[KContextArgs::KTP] = read_current_tp();
             let next_task_cx_ptr = &task_inner.task_cx as *const KContext;
             task_inner.task_status = TaskStatus::Running;
-            // task_inner.memory_set.activate();
-            let token = task_inner.memory_set.token();
+            task_inner.on_cpu = true;
+            let start_us = get_time_us();
+            // the time waiting in the ready queue is not charged
+            task_inner.time_stamp_us = start_us;
+            task_inner.slice_start_us = start_us;
             drop(task_inner);
             // release coming task TCB manually
             processor.current = Some(task);
@@ -55,28 +134,49 @@ pub fn run_tasks() {
             drop(processor);
             // from idel_task, switch to next task with next task's page table
             unsafe { context_switch_pt(idle_task_cx_ptr, next_task_cx_ptr, token) }
+            // the task is switched out and its context is saved now, so it
+            // is safe to let other harts run it
+            let task = take_current_task().unwrap();
+            let mut task_inner = task.inner_exclusive_access();
+            task_inner.sched.account((get_time_us() - start_us) as u64);
+            task_inner.charge_time(false);
+            task_inner.on_cpu = false;
+            let ready = task_inner.task_status == TaskStatus::Ready;
+            drop(task_inner);
+            if ready {
+                add_task(task);
+            }
+        } else {
+            drop(processor);
+            // every task may be blocked, wake them up on time or on input
+            check_timer();
+            poll_stdin();
         }
     }
 }
 
 pub fn take_current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().take_current()
+    current_processor().lock().take_current()
 }
 
 pub fn current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().current()
+    current_processor().lock().current()
+}
+
+pub fn current_process() -> Arc<ProcessControlBlock> {
+    current_task().unwrap().process.clone()
 }
 
 pub fn current_user_token() -> PageTable {
-    let task = current_task().unwrap();
-    let token = task.inner_exclusive_access().get_user_token();
+    let process = current_process();
+    let token = process.inner_exclusive_access().get_user_token();
     token
 }
 
 static BOOT_PAGE_TABLE: LazyInit<PageTable> = LazyInit::new();
 
 pub fn schedule(switched_task_cx_ptr: *mut KContext) {
-    let mut processor = PROCESSOR.exclusive_access();
+    let mut processor = current_processor().lock();
     let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
     drop(processor);
     // from switched task, switch to idle task with kernel page table
@@ -0,0 +1,207 @@
+use super::TaskControlBlock;
+use crate::config::{MAX_TIME_SLICE_TICKS, TIME_SLICE_TICKS};
+use crate::timer::TICK_US;
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+
+/// Nice values range from -20 (favourable) to 19 (unfavourable).
+pub const MIN_NICE: i32 = -20;
+pub const MAX_NICE: i32 = 19;
+
+/// Weight of a task with nice 0.
+const NICE_0_WEIGHT: u64 = 1024;
+/// Weight of `SCHED_IDLE` tasks, lower than the one of nice 19.
+const IDLE_WEIGHT: u64 = 3;
+
+/// Weight of each nice value, every step is about 1.25 times of the next one.
+/// Same as the table of Linux.
+const NICE_TO_WEIGHT: [u64; 40] = [
+    88761, 71755, 56483, 46273, 36291, 29154, 23254, 18705, 14949, 11916, 9548, 7620, 6100, 4904,
+    3906, 3121, 2501, 1991, 1586, 1277, 1024, 820, 655, 526, 423, 335, 272, 215, 172, 137, 110, 87,
+    70, 56, 45, 36, 29, 23, 18, 15,
+];
+
+/// Largest wakeup bonus of CFS in microseconds: a task which has slept for a
+/// long time is put at most this far before the others.
+const CFS_WAKEUP_BONUS_US: u64 = 6000;
+
+/// Scheduling policies, numbered as Linux.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchedPolicy {
+    Normal = 0,
+    /// CPU-bound tasks which get no bonus on wakeup.
+    Batch = 3,
+    /// The smallest weight, so it gets little CPU time while other tasks
+    /// want to run.
+    Idle = 5,
+}
+
+impl SchedPolicy {
+    pub fn from_raw(policy: usize) -> Option<Self> {
+        match policy {
+            0 => Some(Self::Normal),
+            3 => Some(Self::Batch),
+            5 => Some(Self::Idle),
+            _ => None,
+        }
+    }
+}
+
+/// Scheduling state of a task.
+#[derive(Debug, Clone, Copy)]
+pub struct SchedEntity {
+    pub policy: SchedPolicy,
+    pub nice: i32,
+    /// Run time in microseconds scaled by the weight, for CFS.
+    pub vruntime: u64,
+    /// Sum of the strides it has run, for the stride scheduler.
+    pub pass: u64,
+}
+
+impl SchedEntity {
+    pub fn new() -> Self {
+        Self {
+            policy: SchedPolicy::Normal,
+            nice: 0,
+            vruntime: 0,
+            pass: 0,
+        }
+    }
+    pub fn weight(&self) -> u64 {
+        match self.policy {
+            SchedPolicy::Idle => IDLE_WEIGHT,
+            _ => NICE_TO_WEIGHT[(self.nice - MIN_NICE) as usize],
+        }
+    }
+    /// How long the task runs before a timer interrupt preempts it, in
+    /// whole ticks and in proportion to its weight.
+    pub fn time_slice_us(&self) -> usize {
+        let ticks = TIME_SLICE_TICKS as u64 * self.weight() / NICE_0_WEIGHT;
+        ticks.clamp(1, MAX_TIME_SLICE_TICKS as u64) as usize * TICK_US
+    }
+    /// Charge the task for running `delta_us` microseconds.
+    pub fn account(&mut self, delta_us: u64) {
+        self.vruntime += delta_us * NICE_0_WEIGHT / self.weight();
+    }
+}
+
+/// A policy which decides the order to run the ready tasks.
+pub trait Scheduler: Send {
+    /// Put a ready task in the run queue.
+    fn add(&mut self, task: Arc<TaskControlBlock>);
+    /// Pick the task to run next and remove it from the run queue.
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>>;
+}
+
+/// Pick the scheduler by the `SCHED` variable at build time: `rr`, `stride`
+/// or `cfs`, which is the default.
+pub fn new_scheduler() -> Box<dyn Scheduler> {
+    match option_env!("SCHED") {
+        Some("rr") => Box::new(RoundRobin::new()),
+        Some("stride") => Box::new(Stride::new()),
+        _ => Box::new(Cfs::new()),
+    }
+}
+
+/// Run the tasks in turn. The order ignores their weight, which only sets
+/// the length of their time slices.
+pub struct RoundRobin {
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl RoundRobin {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+}
+
+impl Scheduler for RoundRobin {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        self.ready_queue.push_back(task);
+    }
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.ready_queue.pop_front()
+    }
+}
+
+/// Stride scheduling: every time a task is picked its pass grows by a stride
+/// inversely proportional to its weight, and the smallest pass runs first.
+pub struct Stride {
+    // keyed by (pass, seq), seq keeps tasks with the same pass in order
+    ready_queue: BTreeMap<(u64, u64), Arc<TaskControlBlock>>,
+    seq: u64,
+}
+
+impl Stride {
+    /// Stride of the lightest weight, which is 1.
+    const BIG_STRIDE: u64 = 1 << 20;
+
+    pub fn new() -> Self {
+        Self {
+            ready_queue: BTreeMap::new(),
+            seq: 0,
+        }
+    }
+}
+
+impl Scheduler for Stride {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        let pass = task.inner_exclusive_access().sched.pass;
+        self.seq += 1;
+        self.ready_queue.insert((pass, self.seq), task);
+    }
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        let (_, task) = self.ready_queue.pop_first()?;
+        let mut task_inner = task.inner_exclusive_access();
+        let stride = Self::BIG_STRIDE / task_inner.sched.weight();
+        task_inner.sched.pass += stride;
+        drop(task_inner);
+        Some(task)
+    }
+}
+
+/// Completely fair scheduling: the task which has the smallest run time
+/// scaled by its weight runs first, so tasks which sleep a lot like shells
+/// get the CPU at once when they wake up.
+pub struct Cfs {
+    // keyed by (vruntime, seq), seq keeps tasks with the same vruntime in order
+    ready_queue: BTreeMap<(u64, u64), Arc<TaskControlBlock>>,
+    seq: u64,
+    // vruntime of the last picked task, never goes back
+    min_vruntime: u64,
+}
+
+impl Cfs {
+    pub fn new() -> Self {
+        Self {
+            ready_queue: BTreeMap::new(),
+            seq: 0,
+            min_vruntime: 0,
+        }
+    }
+}
+
+impl Scheduler for Cfs {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        let mut task_inner = task.inner_exclusive_access();
+        let sched = &mut task_inner.sched;
+        // don't let a task which slept for long starve the others
+        let floor = match sched.policy {
+            SchedPolicy::Normal => self.min_vruntime.saturating_sub(CFS_WAKEUP_BONUS_US),
+            _ => self.min_vruntime,
+        };
+        sched.vruntime = sched.vruntime.max(floor);
+        let vruntime = sched.vruntime;
+        drop(task_inner);
+        self.seq += 1;
+        self.ready_queue.insert((vruntime, self.seq), task);
+    }
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        let ((vruntime, _), task) = self.ready_queue.pop_first()?;
+        self.min_vruntime = self.min_vruntime.max(vruntime);
+        Some(task)
+    }
+}
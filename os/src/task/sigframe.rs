@@ -0,0 +1,218 @@
+use super::SignalFlags;
+use core::mem::size_of;
+use polyhal_trap::trapframe::TrapFrame;
+
+/// Codes of `SigInfo`: sent by `kill`, or by the kernel.
+pub const SI_USER: i32 = 0;
+pub const SI_KERNEL: i32 = 0x80;
+/// Code of `SigInfo`: sent by `sigqueue`.
+pub const SI_QUEUE: i32 = -1;
+/// Codes of SIGCHLD: the child has exited, been killed, stopped or continued.
+pub const CLD_EXITED: i32 = 1;
+pub const CLD_KILLED: i32 = 2;
+pub const CLD_STOPPED: i32 = 5;
+pub const CLD_CONTINUED: i32 = 6;
+/// Codes of SIGSEGV: the address is not mapped, or doesn't allow the access.
+pub const SEGV_MAPERR: i32 = 1;
+pub const SEGV_ACCERR: i32 = 2;
+/// Code of SIGILL: an illegal opcode.
+pub const ILL_ILLOPC: i32 = 1;
+
+/// Flags of `SignalStack`: the thread runs on it, or it is disabled.
+pub const SS_ONSTACK: i32 = 1;
+pub const SS_DISABLE: i32 = 2;
+/// The smallest alternate stack.
+pub const MINSIGSTKSZ: usize = 2048;
+
+/// Why a signal was sent, laid out as the `siginfo_t` of Linux.
+#[allow(unused)]
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SigInfo {
+    pub signo: i32,
+    pub errno: i32,
+    pub code: i32,
+    _pad: i32,
+    /// Depend on the signal: the pid and uid of the sender in the first
+    /// word, then the status of the child for SIGCHLD or the value given to
+    /// `sigqueue`. For a fault, the
+    /// address, then the `PROT_*` bits of the access for SIGSEGV.
+    pub fields: [usize; 14],
+}
+
+impl SigInfo {
+    pub fn new(signal: SignalFlags, code: i32) -> Self {
+        Self {
+            signo: signal.signum() as i32,
+            errno: 0,
+            code,
+            _pad: 0,
+            fields: [0; 14],
+        }
+    }
+    pub fn signal(&self) -> SignalFlags {
+        SignalFlags::from_bits(1 << self.signo).unwrap()
+    }
+    /// Sent by the kernel, e.g. from the console.
+    pub fn kernel(signal: SignalFlags) -> Self {
+        Self::new(signal, SI_KERNEL)
+    }
+    /// Sent by process `pid` with `kill`. Every process belongs to root.
+    pub fn user(signal: SignalFlags, pid: usize) -> Self {
+        let mut info = Self::new(signal, SI_USER);
+        info.fields[0] = pid;
+        info
+    }
+    /// Sent by process `pid` with `sigqueue`, along with `value`.
+    pub fn queue(signal: SignalFlags, pid: usize, value: usize) -> Self {
+        let mut info = Self::new(signal, SI_QUEUE);
+        info.fields[0] = pid;
+        info.fields[1] = value;
+        info
+    }
+    /// A fault at `addr`, which is the instruction for SIGILL.
+    pub fn fault(signal: SignalFlags, code: i32, addr: usize) -> Self {
+        let mut info = Self::new(signal, code);
+        info.fields[0] = addr;
+        info
+    }
+    /// SIGCHLD about child `pid`, `status` is the exit code or the signal.
+    pub fn child(pid: usize, code: i32, status: i32) -> Self {
+        let mut info = Self::new(SignalFlags::SIGCHLD, code);
+        info.fields[0] = pid;
+        info.fields[1] = status as usize;
+        info
+    }
+}
+
+/// An alternate stack for handlers, laid out as the `stack_t` of Linux.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SignalStack {
+    pub sp: usize,
+    pub flags: i32,
+    pub size: usize,
+}
+
+impl SignalStack {
+    pub fn disabled() -> Self {
+        Self {
+            sp: 0,
+            flags: SS_DISABLE,
+            size: 0,
+        }
+    }
+    pub fn enabled(&self) -> bool {
+        self.flags & SS_DISABLE == 0
+    }
+    /// Whether a thread with the stack pointer `sp` runs on this stack.
+    pub fn contains(&self, sp: usize) -> bool {
+        self.enabled() && sp > self.sp && sp - self.sp <= self.size
+    }
+    /// The stack as reported to a thread with the stack pointer `sp`.
+    pub fn status(&self, sp: usize) -> Self {
+        let mut stack = *self;
+        if self.contains(sp) {
+            stack.flags = SS_ONSTACK;
+        }
+        stack
+    }
+}
+
+/// The registers of the interrupted context, copied from the trap frame.
+pub const MCONTEXT_WORDS: usize = size_of::<TrapFrame>().div_ceil(size_of::<usize>());
+
+/// The context a handler returns to, like the `ucontext_t` of Linux.
+#[allow(unused)]
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct UContext {
+    pub flags: usize,
+    pub link: usize,
+    /// The alternate stack when the signal arrived.
+    pub stack: SignalStack,
+    /// The signal mask restored when the handler returns, which the handler
+    /// may change.
+    pub sigmask: u64,
+    pub mcontext: [usize; MCONTEXT_WORDS],
+}
+
+impl UContext {
+    /// The context to return to: the registers of `mcontext`, which the
+    /// handler may change, with the privileged state of `trap_cx`, so that
+    /// the thread stays in user mode.
+    pub fn trap_cx(&self, trap_cx: &TrapFrame) -> TrapFrame {
+        let mut restored = trap_cx.clone();
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                self.mcontext.as_ptr() as *const u8,
+                &mut restored as *mut TrapFrame as *mut u8,
+                size_of::<TrapFrame>(),
+            );
+        }
+        keep_privileged_state(&mut restored, trap_cx);
+        restored
+    }
+}
+
+#[cfg(target_arch = "riscv64")]
+fn keep_privileged_state(restored: &mut TrapFrame, trap_cx: &TrapFrame) {
+    restored.sstatus = trap_cx.sstatus;
+}
+
+#[cfg(target_arch = "aarch64")]
+fn keep_privileged_state(restored: &mut TrapFrame, trap_cx: &TrapFrame) {
+    // only the condition flags NZCV belong to the user
+    const USER_SPSR: usize = 0xf000_0000;
+    restored.spsr = (trap_cx.spsr & !USER_SPSR) | (restored.spsr & USER_SPSR);
+}
+
+#[cfg(target_arch = "loongarch64")]
+fn keep_privileged_state(restored: &mut TrapFrame, trap_cx: &TrapFrame) {
+    restored.prmd = trap_cx.prmd;
+}
+
+#[cfg(target_arch = "x86_64")]
+fn keep_privileged_state(restored: &mut TrapFrame, trap_cx: &TrapFrame) {
+    // only the arithmetic flags and DF belong to the user
+    const USER_RFLAGS: usize = 0xcd5;
+    restored.rflags = (trap_cx.rflags & !USER_RFLAGS) | (restored.rflags & USER_RFLAGS);
+    restored.cs = trap_cx.cs;
+    restored.ss = trap_cx.ss;
+}
+
+/// What is pushed on the user stack to run a handler.
+#[repr(C, align(16))]
+#[derive(Clone, Copy)]
+pub struct SignalFrame {
+    pub info: SigInfo,
+    pub ucontext: UContext,
+}
+
+impl SignalFrame {
+    pub fn new(
+        info: SigInfo,
+        trap_cx: &TrapFrame,
+        signal_mask: SignalFlags,
+        stack: SignalStack,
+    ) -> Self {
+        let mut mcontext = [0usize; MCONTEXT_WORDS];
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                trap_cx as *const TrapFrame as *const u8,
+                mcontext.as_mut_ptr() as *mut u8,
+                size_of::<TrapFrame>(),
+            );
+        }
+        Self {
+            info,
+            ucontext: UContext {
+                flags: 0,
+                link: 0,
+                stack,
+                sigmask: signal_mask.bits(),
+                mcontext,
+            },
+        }
+    }
+}
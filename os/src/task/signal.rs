@@ -1,9 +1,11 @@
 use bitflags::*;
 
-pub const MAX_SIG: usize = 31;
+pub const MAX_SIG: usize = 63;
+/// The real-time signals, which are queued each time they are sent.
+pub const SIGRTMIN: usize = 32;
 
 bitflags! {
-    pub struct SignalFlags: u32 {
+    pub struct SignalFlags: u64 {
         const SIGDEF = 1; // Default signal handling
         const SIGHUP = 1 << 1;
         const SIGINT = 1 << 2;
@@ -36,26 +38,86 @@ bitflags! {
         const SIGIO = 1 << 29;
         const SIGPWR = 1 << 30;
         const SIGSYS = 1 << 31;
+        /// All the real-time signals, from SIGRTMIN to MAX_SIG.
+        const SIGRT = !0 << SIGRTMIN;
     }
 }
 
 impl SignalFlags {
-    pub fn check_error(&self) -> Option<(i32, &'static str)> {
-        if self.contains(Self::SIGINT) {
-            Some((-2, "Killed, SIGINT=2"))
-        } else if self.contains(Self::SIGILL) {
-            Some((-4, "Illegal Instruction, SIGILL=4"))
-        } else if self.contains(Self::SIGABRT) {
-            Some((-6, "Aborted, SIGABRT=6"))
-        } else if self.contains(Self::SIGFPE) {
-            Some((-8, "Erroneous Arithmetic Operation, SIGFPE=8"))
-        } else if self.contains(Self::SIGKILL) {
-            Some((-9, "Killed, SIGKILL=9"))
-        } else if self.contains(Self::SIGSEGV) {
-            Some((-11, "Segmentation Fault, SIGSEGV=11"))
-        } else {
-            //println!("[K] signalflags check_error  {:?}", self);
-            None
+    /// The number of a single signal.
+    pub fn signum(&self) -> usize {
+        self.bits().trailing_zeros() as usize
+    }
+    /// Whether it is a single real-time signal.
+    pub fn is_realtime(&self) -> bool {
+        self.intersects(Self::SIGRT)
+    }
+    /// The signals which can't be blocked.
+    pub fn unblockable() -> Self {
+        Self::SIGKILL | Self::SIGSTOP
+    }
+    /// What the signal does to a process which doesn't catch it.
+    pub fn default_action(&self) -> DefaultAction {
+        match *self {
+            Self::SIGCHLD | Self::SIGURG | Self::SIGWINCH => DefaultAction::Ignore,
+            Self::SIGCONT => DefaultAction::Continue,
+            Self::SIGSTOP | Self::SIGTSTP | Self::SIGTTIN | Self::SIGTTOU => DefaultAction::Stop,
+            Self::SIGQUIT
+            | Self::SIGILL
+            | Self::SIGTRAP
+            | Self::SIGABRT
+            | Self::SIGBUS
+            | Self::SIGFPE
+            | Self::SIGSEGV
+            | Self::SIGXCPU
+            | Self::SIGXFSZ
+            | Self::SIGSYS => DefaultAction::CoreDump,
+            _ => DefaultAction::Terminate,
         }
     }
+    /// Why a process killed by the signal has exited.
+    pub fn description(&self) -> &'static str {
+        match *self {
+            Self::SIGHUP => "Hangup, SIGHUP=1",
+            Self::SIGINT => "Killed, SIGINT=2",
+            Self::SIGQUIT => "Quit, SIGQUIT=3",
+            Self::SIGILL => "Illegal Instruction, SIGILL=4",
+            Self::SIGTRAP => "Trace/Breakpoint Trap, SIGTRAP=5",
+            Self::SIGABRT => "Aborted, SIGABRT=6",
+            Self::SIGBUS => "Bus Error, SIGBUS=7",
+            Self::SIGFPE => "Erroneous Arithmetic Operation, SIGFPE=8",
+            Self::SIGKILL => "Killed, SIGKILL=9",
+            Self::SIGUSR1 => "User Defined Signal 1, SIGUSR1=10",
+            Self::SIGSEGV => "Segmentation Fault, SIGSEGV=11",
+            Self::SIGUSR2 => "User Defined Signal 2, SIGUSR2=12",
+            Self::SIGPIPE => "Broken Pipe, SIGPIPE=13",
+            Self::SIGALRM => "Alarm Clock, SIGALRM=14",
+            Self::SIGTERM => "Terminated, SIGTERM=15",
+            Self::SIGSTKFLT => "Stack Fault, SIGSTKFLT=16",
+            Self::SIGXCPU => "CPU Time Limit Exceeded, SIGXCPU=24",
+            Self::SIGXFSZ => "File Size Limit Exceeded, SIGXFSZ=25",
+            Self::SIGVTALRM => "Virtual Timer Expired, SIGVTALRM=26",
+            Self::SIGPROF => "Profiling Timer Expired, SIGPROF=27",
+            Self::SIGIO => "I/O Possible, SIGIO=29",
+            Self::SIGPWR => "Power Failure, SIGPWR=30",
+            Self::SIGSYS => "Bad System Call, SIGSYS=31",
+            signal if signal.is_realtime() => "Real-time Signal",
+            _ => "Killed by a signal",
+        }
+    }
+}
+
+/// The actions of POSIX for signals which are not caught.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefaultAction {
+    /// Terminate the process.
+    Terminate,
+    /// Terminate the process, which would dump a core.
+    CoreDump,
+    /// Discard the signal.
+    Ignore,
+    /// Stop the process until SIGCONT.
+    Stop,
+    /// Continue the process if it is stopped.
+    Continue,
 }
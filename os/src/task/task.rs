@@ -1,25 +1,27 @@
-use super::{current_task, SignalActions};
-use super::{pid_alloc, PidHandle, SignalFlags};
+use super::scheduler::SchedEntity;
+use super::sigframe::SignalStack;
+use super::{current_task, enter_user, ProcessControlBlock, SignalFlags, SignalQueue};
 use crate::config::KERNEL_STACK_SIZE;
-use crate::fs::{File, Stdin, Stdout};
-use crate::mm::{translated_refmut, MemorySet};
-use crate::sync::UPSafeCell;
-use alloc::string::String;
-use alloc::sync::{Arc, Weak};
-use alloc::vec;
+use crate::timer::get_time_us;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
-use core::cell::RefMut;
 use core::mem::size_of;
+use core::ops::DerefMut;
 use polyhal::kcontext::{read_current_tp, KContext, KContextArgs};
-use polyhal::pagetable::PageTable;
+use polyhal::utils::MutexNoIrq;
 use polyhal_trap::trap::run_user_task;
 use polyhal_trap::trapframe::{TrapFrame, TrapFrameArgs};
 
 pub struct TaskControlBlock {
     // immutable
-    pub pid: PidHandle,
+    pub process: Arc<ProcessControlBlock>,
+    pub tid: usize,
+    // top of the user stack, which is unmapped when a thread other than the
+    // main one exits
+    pub ustack_top: usize,
+    pub kernel_stack: KernelStack,
     // mutable
-    inner: UPSafeCell<TaskControlBlockInner>,
+    inner: MutexNoIrq<TaskControlBlockInner>,
 }
 use log::*;
 impl Drop for TaskControlBlock {
@@ -47,26 +49,33 @@ impl KernelStack {
 
 pub struct TaskControlBlockInner {
     pub trap_cx: TrapFrame,
-    pub base_size: usize,
     pub task_cx: KContext,
     pub task_status: TaskStatus,
-    pub memory_set: MemorySet,
-    pub kernel_stack: KernelStack,
-    pub parent: Option<Weak<TaskControlBlock>>,
-    pub children: Vec<Arc<TaskControlBlock>>,
-    pub exit_code: i32,
-    pub fd_table: Vec<Option<Arc<dyn File + Send + Sync>>>,
-    pub signals: SignalFlags,
+    // still running on a hart, even if the status has changed
+    pub on_cpu: bool,
+    // woken up before it blocks, so it should not block this time
+    pub wakeup_pending: bool,
+    // scheduling policy, weight and the run time of the scheduler
+    pub sched: SchedEntity,
+    // set once the thread exits, for waittid
+    pub exit_code: Option<i32>,
+    // time run in user and kernel mode in microseconds, moved to the
+    // process when the thread exits
+    pub utime_us: usize,
+    pub stime_us: usize,
+    // since when the time has not been charged
+    pub time_stamp_us: usize,
+    // when it was switched in last time, its time slice starts then
+    pub slice_start_us: usize,
     pub signal_mask: SignalFlags,
-    // the signal which is being handling
-    pub handling_sig: isize,
-    // Signal actions
-    pub signal_actions: SignalActions,
-    // if the task is killed
-    pub killed: bool,
-    // if the task is frozen by a signal
-    pub frozen: bool,
-    pub trap_ctx_backup: Option<TrapFrame>,
+    // the mask before sigsuspend, which comes back after the handler
+    pub saved_mask: Option<SignalFlags>,
+    // signals sent to the thread itself, e.g. for its faults
+    pub pending: SignalQueue,
+    // where handlers with SA_ONSTACK run
+    pub signal_stack: SignalStack,
+    // where the frames of the running handlers are, the innermost last
+    pub signal_frames: Vec<usize>,
 }
 
 impl TaskControlBlockInner {
@@ -76,35 +85,46 @@ impl TaskControlBlockInner {
         // unsafe { paddr.get_mut_ptr::<TrapFrame>().as_mut().unwrap() }
         unsafe { paddr.as_mut().unwrap() }
     }
-    pub fn get_user_token(&self) -> PageTable {
-        self.memory_set.token()
-    }
-    fn get_status(&self) -> TaskStatus {
-        self.task_status
-    }
-    pub fn is_zombie(&self) -> bool {
-        self.get_status() == TaskStatus::Zombie
+    /// Forget the frames of the handlers left without sigreturn, e.g. by
+    /// siglongjmp, as seen from the stack pointer `sp`: those below it on
+    /// the stack it is on, and those on the alternate stack when it is off.
+    pub fn drop_left_frames(&mut self, sp: usize) {
+        let stack = self.signal_stack;
+        let on_stack = stack.contains(sp);
+        self.signal_frames.retain(|&frame| {
+            if stack.contains(frame) {
+                on_stack && frame >= sp
+            } else {
+                on_stack || frame >= sp
+            }
+        });
     }
-    pub fn alloc_fd(&mut self) -> usize {
-        if let Some(fd) = (0..self.fd_table.len()).find(|fd| self.fd_table[*fd].is_none()) {
-            fd
+    /// Charge the time since the last call as user or system time.
+    pub fn charge_time(&mut self, user: bool) {
+        let now = get_time_us();
+        let delta = now - self.time_stamp_us;
+        if user {
+            self.utime_us += delta;
         } else {
-            self.fd_table.push(None);
-            self.fd_table.len() - 1
+            self.stime_us += delta;
         }
+        self.time_stamp_us = now;
     }
 }
 
 fn task_entry() {
     trace!("os::task::task_entry");
-    let task = current_task()
-        .unwrap()
-        .inner
-        .exclusive_access()
-        .get_trap_cx() as *mut TrapFrame;
+    let task = current_task().unwrap().inner.lock().get_trap_cx() as *mut TrapFrame;
     // run_user_task_forever(unsafe { task.as_mut().unwrap() })
     let ctx_mut = unsafe { task.as_mut().unwrap() };
     loop {
+        let task = current_task().unwrap();
+        // the time in the kernel ends here, the trap handler charges the
+        // time in user mode
+        task.inner_exclusive_access().charge_time(false);
+        let space = task.process.inner_exclusive_access().memory_set.space_id();
+        drop(task);
+        enter_user(space);
         run_user_task(ctx_mut);
     }
 }
@@ -118,154 +138,49 @@ fn blank_kcontext(ksp: usize) -> KContext {
 }
 
 impl TaskControlBlock {
-    pub fn inner_exclusive_access(&self) -> RefMut<'_, TaskControlBlockInner> {
-        self.inner.exclusive_access()
+    pub fn inner_exclusive_access(&self) -> impl DerefMut<Target = TaskControlBlockInner> + '_ {
+        self.inner.lock()
     }
-    pub fn new(elf_data: &[u8]) -> Self {
+    /// Create a ready thread of `process` which starts with the user stack
+    /// at `ustack_top`. The caller sets the entry point in its trap context.
+    pub fn new(
+        process: Arc<ProcessControlBlock>,
+        tid: usize,
+        ustack_top: usize,
+        sched: SchedEntity,
+        signal_mask: SignalFlags,
+    ) -> Self {
         trace!("os::task::TaskControlBlock::new");
-        // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
-        // alloc a pid and a kernel stack in kernel space
-        let pid_handle = pid_alloc();
         let kstack = KernelStack::new();
-        let task_control_block = Self {
-            pid: pid_handle,
-            inner: unsafe {
-                UPSafeCell::new(TaskControlBlockInner {
-                    trap_cx: TrapFrame::new(),
-                    base_size: user_sp,
-                    task_cx: blank_kcontext(kstack.get_position().1), // Set task_cx's Kernel Stack Top
-                    task_status: TaskStatus::Ready,
-                    memory_set,
-                    parent: None,
-                    children: Vec::new(),
-                    exit_code: 0,
-                    fd_table: vec![
-                        // 0 -> stdin
-                        Some(Arc::new(Stdin)),
-                        // 1 -> stdout
-                        Some(Arc::new(Stdout)),
-                        // 2 -> stderr
-                        Some(Arc::new(Stdout)),
-                    ],
-                    signals: SignalFlags::empty(),
-                    signal_mask: SignalFlags::empty(),
-                    handling_sig: -1,
-                    signal_actions: SignalActions::default(),
-                    killed: false,
-                    frozen: false,
-                    trap_ctx_backup: None,
-                    kernel_stack: kstack,
-                })
-            },
-        };
-        // prepare TrapContext in user space
-        let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
-        trap_cx[TrapFrameArgs::SEPC] = entry_point;
-        trap_cx[TrapFrameArgs::SP] = user_sp;
-        task_control_block
-    }
-    pub fn exec(&self, elf_data: &[u8], args: Vec<String>) {
-        trace!("os::task::TaskControlBlock::exec");
-        // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, mut user_sp, entry_point) = MemorySet::from_elf(elf_data);
-        memory_set.activate();
-        // push arguments on user stack
-        user_sp -= (args.len() + 1) * core::mem::size_of::<usize>();
-        let argv_base = user_sp;
-        let mut argv: Vec<_> = (0..=args.len())
-            .map(|arg| {
-                translated_refmut(
-                    memory_set.token(),
-                    (argv_base + arg * core::mem::size_of::<usize>()) as *mut usize,
-                )
-            })
-            .collect();
-        *argv[args.len()] = 0;
-        for i in 0..args.len() {
-            user_sp -= args[i].len() + 1;
-            *argv[i] = user_sp;
-            let mut p = user_sp;
-            for c in args[i].as_bytes() {
-                *translated_refmut(memory_set.token(), p as *mut u8) = *c;
-                p += 1;
-            }
-            *translated_refmut(memory_set.token(), p as *mut u8) = 0;
-        }
-        // make the user_sp aligned to 8B for k210 platform
-        user_sp -= user_sp % core::mem::size_of::<usize>();
-        memory_set.activate();
-
-        // **** access current TCB exclusively
-        let mut inner = self.inner_exclusive_access();
-        // substitute memory_set
-        inner.memory_set = memory_set;
-        // update trap_cx ppn
-        // FIXME: This is a temporary solution
-        inner.trap_cx = TrapFrame::new();
-        // initialize trap_cx
         let mut trap_cx = TrapFrame::new();
-        trap_cx[TrapFrameArgs::SEPC] = entry_point;
-        trap_cx[TrapFrameArgs::SP] = user_sp;
-        trap_cx[TrapFrameArgs::ARG0] = args.len();
-        trap_cx[TrapFrameArgs::ARG1] = argv_base;
-        *inner.get_trap_cx() = trap_cx;
-        // **** release current PCB
-    }
-    pub fn fork(self: &Arc<TaskControlBlock>) -> Arc<TaskControlBlock> {
-        trace!("os::task::TaskControlBlock::fork");
-        // ---- hold parent PCB lock
-        let mut parent_inner = self.inner_exclusive_access();
-        // copy user space(include trap context)
-        let memory_set = MemorySet::from_existed_user(&parent_inner.memory_set);
-
-        // alloc a pid and a kernel stack in kernel space
-        let pid_handle = pid_alloc();
-        let kstack = KernelStack::new();
-        // copy fd table
-        let mut new_fd_table: Vec<Option<Arc<dyn File + Send + Sync>>> = Vec::new();
-        for fd in parent_inner.fd_table.iter() {
-            if let Some(file) = fd {
-                new_fd_table.push(Some(file.clone()));
-            } else {
-                new_fd_table.push(None);
-            }
+        trap_cx[TrapFrameArgs::SP] = ustack_top;
+        Self {
+            process,
+            tid,
+            ustack_top,
+            inner: MutexNoIrq::new(TaskControlBlockInner {
+                trap_cx,
+                task_cx: blank_kcontext(kstack.get_position().1), // Set task_cx's Kernel Stack Top
+                task_status: TaskStatus::Ready,
+                on_cpu: false,
+                wakeup_pending: false,
+                sched,
+                exit_code: None,
+                utime_us: 0,
+                stime_us: 0,
+                time_stamp_us: get_time_us(),
+                slice_start_us: 0,
+                signal_mask,
+                saved_mask: None,
+                pending: SignalQueue::default(),
+                signal_stack: SignalStack::disabled(),
+                signal_frames: Vec::new(),
+            }),
+            kernel_stack: kstack,
         }
-        let task_control_block = Arc::new(TaskControlBlock {
-            pid: pid_handle,
-            inner: unsafe {
-                UPSafeCell::new(TaskControlBlockInner {
-                    trap_cx: parent_inner.trap_cx.clone(),
-                    base_size: parent_inner.base_size,
-                    task_cx: blank_kcontext(kstack.get_position().1), // Set task_cx's Kernel Stack Top
-                    task_status: TaskStatus::Ready,
-                    memory_set,
-                    parent: Some(Arc::downgrade(self)),
-                    children: Vec::new(),
-                    exit_code: 0,
-                    fd_table: new_fd_table,
-                    signals: SignalFlags::empty(),
-                    // inherit the signal_mask and signal_action
-                    signal_mask: parent_inner.signal_mask,
-                    handling_sig: -1,
-                    signal_actions: parent_inner.signal_actions.clone(),
-                    killed: false,
-                    frozen: false,
-                    trap_ctx_backup: None,
-                    kernel_stack: kstack,
-                })
-            },
-        });
-        // add child
-        parent_inner.children.push(task_control_block.clone());
-        // modify kernel_sp in trap_cx
-        // **** access child PCB exclusively
-        task_control_block
-        // **** release child PCB
-        // ---- release parent PCB
     }
     pub fn getpid(&self) -> usize {
-        self.pid.0
+        self.process.getpid()
     }
 }
 
@@ -273,5 +188,6 @@ impl TaskControlBlock {
 pub enum TaskStatus {
     Ready,
     Running,
+    Blocked,
     Zombie,
 }
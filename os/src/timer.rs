@@ -0,0 +1,230 @@
+use crate::config::TICKS_PER_SEC;
+use crate::task::{real_timer_expired, wakeup_task, ProcessControlBlock, TaskControlBlock};
+use alloc::collections::BinaryHeap;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use lazy_static::*;
+use polyhal::time::Time;
+use polyhal::utils::MutexNoIrq;
+
+const USEC_PER_SEC: usize = 1_000_000;
+const NSEC_PER_USEC: usize = 1_000;
+/// Length of a scheduler tick in microseconds.
+pub const TICK_US: usize = USEC_PER_SEC / TICKS_PER_SEC;
+
+/// Get the time since boot in microseconds.
+pub fn get_time_us() -> usize {
+    Time::now().to_usec()
+}
+
+/// `struct timespec` of Linux.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+impl TimeSpec {
+    pub fn from_us(us: usize) -> Self {
+        Self {
+            sec: us / USEC_PER_SEC,
+            nsec: us % USEC_PER_SEC * NSEC_PER_USEC,
+        }
+    }
+    /// Round up to whole microseconds, or `None` if `nsec` is out of range.
+    pub fn to_us(&self) -> Option<usize> {
+        if self.nsec >= USEC_PER_SEC * NSEC_PER_USEC {
+            return None;
+        }
+        Some(self.sec * USEC_PER_SEC + self.nsec.div_ceil(NSEC_PER_USEC))
+    }
+}
+
+/// `struct timeval` of Linux.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeVal {
+    pub sec: usize,
+    pub usec: usize,
+}
+
+impl TimeVal {
+    pub fn from_us(us: usize) -> Self {
+        Self {
+            sec: us / USEC_PER_SEC,
+            usec: us % USEC_PER_SEC,
+        }
+    }
+    /// `None` if `usec` is out of range.
+    pub fn to_us(&self) -> Option<usize> {
+        if self.usec >= USEC_PER_SEC {
+            return None;
+        }
+        Some(self.sec * USEC_PER_SEC + self.usec)
+    }
+}
+
+/// The interval timers of a process: counting real time and raising
+/// SIGALRM, user time and SIGVTALRM, or user and system time and SIGPROF.
+pub const ITIMER_REAL: usize = 0;
+pub const ITIMER_VIRTUAL: usize = 1;
+pub const ITIMER_PROF: usize = 2;
+
+/// `struct itimerval` of Linux.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ITimerVal {
+    pub interval: TimeVal,
+    pub value: TimeVal,
+}
+
+/// An interval timer, which expires once its clock reaches `expire_us` and
+/// then every `interval_us` unless it is 0. It is disarmed if `expire_us` is
+/// 0.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IntervalTimer {
+    pub expire_us: usize,
+    pub interval_us: usize,
+}
+
+impl IntervalTimer {
+    /// Set by `value` at `now`.
+    pub fn new(now: usize, value: &ITimerVal) -> Option<Self> {
+        let value_us = value.value.to_us()?;
+        Some(Self {
+            expire_us: if value_us == 0 { 0 } else { now + value_us },
+            interval_us: value.interval.to_us()?,
+        })
+    }
+    pub fn armed(&self) -> bool {
+        self.expire_us != 0
+    }
+    /// The time left at `now`, and the interval.
+    pub fn value(&self, now: usize) -> ITimerVal {
+        let value_us = if self.armed() {
+            // it is about to expire
+            self.expire_us.saturating_sub(now).max(1)
+        } else {
+            0
+        };
+        ITimerVal {
+            interval: TimeVal::from_us(self.interval_us),
+            value: TimeVal::from_us(value_us),
+        }
+    }
+    /// Whether it has expired at `now`, then arm it for the next interval.
+    /// The expirations which have been missed meanwhile are lost.
+    pub fn expire(&mut self, now: usize) -> bool {
+        if !self.armed() || now < self.expire_us {
+            return false;
+        }
+        self.expire_us = if self.interval_us == 0 {
+            0
+        } else {
+            let missed = (now - self.expire_us) / self.interval_us;
+            self.expire_us + (missed + 1) * self.interval_us
+        };
+        true
+    }
+}
+
+/// A task waiting in the timer queue until `expire_us`.
+pub struct TimerCondVar {
+    pub expire_us: usize,
+    pub task: Arc<TaskControlBlock>,
+}
+
+impl PartialEq for TimerCondVar {
+    fn eq(&self, other: &Self) -> bool {
+        self.expire_us == other.expire_us
+    }
+}
+impl Eq for TimerCondVar {}
+impl PartialOrd for TimerCondVar {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TimerCondVar {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max-heap, reverse it to pop the earliest timer first
+        other.expire_us.cmp(&self.expire_us)
+    }
+}
+
+lazy_static! {
+    static ref TIMERS: MutexNoIrq<BinaryHeap<TimerCondVar>> =
+        MutexNoIrq::new(BinaryHeap::<TimerCondVar>::new());
+}
+
+/// Wake up `task` once the time reaches `expire_us`.
+pub fn add_timer(expire_us: usize, task: Arc<TaskControlBlock>) {
+    let mut timers = TIMERS.lock();
+    timers.push(TimerCondVar { expire_us, task });
+}
+
+/// Drop the timers of `task`, e.g. when it is woken up early.
+pub fn remove_timer(task: &Arc<TaskControlBlock>) {
+    let mut timers = TIMERS.lock();
+    timers.retain(|condvar| !Arc::ptr_eq(&condvar.task, task));
+}
+
+/// A process whose ITIMER_REAL expires at `expire_us`.
+struct RealTimer {
+    expire_us: usize,
+    process: Weak<ProcessControlBlock>,
+}
+
+lazy_static! {
+    static ref REAL_TIMERS: MutexNoIrq<Vec<RealTimer>> = MutexNoIrq::new(Vec::new());
+}
+
+/// Tell `process` once the time reaches `expire_us` that its ITIMER_REAL
+/// has expired.
+pub fn add_real_timer(expire_us: usize, process: &Arc<ProcessControlBlock>) {
+    let mut real_timers = REAL_TIMERS.lock();
+    real_timers.push(RealTimer {
+        expire_us,
+        process: Arc::downgrade(process),
+    });
+}
+
+/// Drop the real timers of `process`, when its ITIMER_REAL changes.
+pub fn remove_real_timer(process: &Arc<ProcessControlBlock>) {
+    let mut real_timers = REAL_TIMERS.lock();
+    real_timers.retain(|timer| !core::ptr::eq(timer.process.as_ptr(), Arc::as_ptr(process)));
+}
+
+/// Wake up all the tasks whose timer has expired, and tell the processes
+/// whose real timer has.
+pub fn check_timer() {
+    let current_us = get_time_us();
+    let mut expired = Vec::new();
+    let mut timers = TIMERS.lock();
+    while let Some(timer) = timers.peek() {
+        if timer.expire_us > current_us {
+            break;
+        }
+        expired.push(timers.pop().unwrap());
+    }
+    drop(timers);
+    for timer in expired {
+        wakeup_task(timer.task);
+    }
+    let mut processes = Vec::new();
+    let mut real_timers = REAL_TIMERS.lock();
+    real_timers.retain(|timer| {
+        if timer.expire_us > current_us {
+            return true;
+        }
+        processes.extend(timer.process.upgrade());
+        false
+    });
+    drop(real_timers);
+    for process in processes {
+        real_timer_expired(&process);
+    }
+}
@@ -0,0 +1,57 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{check, Errno};
+use user_lib::*;
+
+/// Keep the CPU busy for `ms` milliseconds of wall time.
+fn spin(ms: isize) {
+    let end = get_time() + ms;
+    while get_time() < end {}
+}
+
+fn cpu_time_us(who: isize) -> usize {
+    let mut usage = Rusage::default();
+    assert_eq!(getrusage(who, &mut usage), 0);
+    usage.utime.to_us() + usage.stime.to_us()
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut usage = Rusage::default();
+    assert_eq!(check(getrusage(5, &mut usage)), Err(Errno::EINVAL));
+
+    let before = cpu_time_us(RUSAGE_SELF);
+    let start = get_time();
+    spin(100);
+    let wall_us = (get_time() - start) as usize * 1000;
+    let busy = cpu_time_us(RUSAGE_SELF) - before;
+    // the other harts keep the tests which run at the same time
+    assert!(busy >= 50_000, "busy for {}us only", busy);
+    assert!(busy <= wall_us + 10_000, "{}us in {}us", busy, wall_us);
+    assert!(cpu_time_us(RUSAGE_THREAD) <= cpu_time_us(RUSAGE_SELF));
+
+    // sleeping takes no CPU time
+    let before = cpu_time_us(RUSAGE_SELF);
+    sleep(100);
+    assert!(cpu_time_us(RUSAGE_SELF) - before < 20_000);
+
+    assert_eq!(cpu_time_us(RUSAGE_CHILDREN), 0);
+    let pid = fork();
+    if pid == 0 {
+        spin(100);
+        exit(0);
+    }
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert!(cpu_time_us(RUSAGE_CHILDREN) >= 50_000);
+    let mut tms = Tms::default();
+    assert!(times(&mut tms) > 0);
+    assert!(tms.cutime + tms.cstime >= 5 * CLOCKS_PER_SEC / 100);
+    assert!(tms.utime + tms.stime >= 5 * CLOCKS_PER_SEC / 100);
+    println!("cpu_times passed!");
+    0
+}
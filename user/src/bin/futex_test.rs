@@ -0,0 +1,96 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use user_lib::errno::{check, Errno};
+use user_lib::sync::{Condvar, Mutex, Semaphore};
+use user_lib::*;
+
+const THREADS: usize = 4;
+const ROUNDS: usize = 200;
+
+static MUTEX: Mutex = Mutex::new();
+// only updated with MUTEX held, in two steps to catch a broken lock
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+fn add_counter(_arg: usize) -> ! {
+    for _ in 0..ROUNDS {
+        MUTEX.lock();
+        let value = COUNTER.load(Ordering::Relaxed);
+        yield_();
+        COUNTER.store(value + 1, Ordering::Relaxed);
+        MUTEX.unlock();
+    }
+    exit(0)
+}
+
+static READY: AtomicU32 = AtomicU32::new(0);
+static CONDVAR: Condvar = Condvar::new();
+
+fn notifier(_arg: usize) -> ! {
+    sleep(50);
+    MUTEX.lock();
+    READY.store(1, Ordering::Relaxed);
+    CONDVAR.notify_all();
+    MUTEX.unlock();
+    exit(0)
+}
+
+static SEM: Semaphore = Semaphore::new(0);
+static PRODUCED: AtomicUsize = AtomicUsize::new(0);
+
+fn producer(_arg: usize) -> ! {
+    for _ in 0..ROUNDS {
+        PRODUCED.fetch_add(1, Ordering::SeqCst);
+        SEM.up();
+    }
+    exit(0)
+}
+
+fn join(tid: isize) {
+    let mut exit_code = -1;
+    assert_eq!(waittid(tid as usize, &mut exit_code), tid);
+    assert_eq!(exit_code, 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // the value has changed, or nobody wakes us up in time
+    let word = AtomicU32::new(1);
+    assert_eq!(check(futex_wait(&word, 0, None)), Err(Errno::EAGAIN));
+    let start = get_time();
+    let timeout = TimeSpec::from_ms(50);
+    assert_eq!(
+        check(futex_wait(&word, 1, Some(&timeout))),
+        Err(Errno::ETIMEDOUT)
+    );
+    assert!(get_time() - start >= 50);
+    assert_eq!(futex_wake(&word, 1), 0);
+
+    let mut tids = [0isize; THREADS];
+    for tid in tids.iter_mut() {
+        *tid = thread_create(add_counter as usize, 0);
+    }
+    tids.iter().for_each(|tid| join(*tid));
+    assert_eq!(COUNTER.load(Ordering::Relaxed), THREADS * ROUNDS);
+
+    let tid = thread_create(notifier as usize, 0);
+    MUTEX.lock();
+    while READY.load(Ordering::Relaxed) == 0 {
+        CONDVAR.wait(&MUTEX);
+    }
+    MUTEX.unlock();
+    join(tid);
+
+    let tid = thread_create(producer as usize, 0);
+    for _ in 0..ROUNDS {
+        SEM.down();
+    }
+    assert_eq!(PRODUCED.load(Ordering::SeqCst), ROUNDS);
+    join(tid);
+    println!("futex_test passed!");
+    0
+}
@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::sbrk;
+
+const LEN: usize = 0x40000;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let brk = sbrk(0);
+    assert!(brk > 0);
+    // a break which doesn't fit in the user space is refused
+    assert_eq!(user_lib::brk(usize::MAX), brk);
+    assert_eq!(user_lib::brk(usize::MAX - 0x800), brk);
+    assert_eq!(sbrk(0), brk);
+    // far larger than the initial heap of user_lib
+    let mut v: Vec<usize> = Vec::with_capacity(LEN);
+    for i in 0..LEN {
+        v.push(i);
+    }
+    assert_eq!(v.iter().sum::<usize>(), LEN * (LEN - 1) / 2);
+    assert!(sbrk(0) > brk);
+    drop(v);
+    let mut chunks: Vec<Vec<u8>> = Vec::new();
+    for i in 0..16 {
+        chunks.push(alloc::vec![i as u8; 0x4000]);
+    }
+    for (i, chunk) in chunks.iter().enumerate() {
+        assert!(chunk.iter().all(|byte| *byte == i as u8));
+    }
+    println!("heap_grow passed!");
+    0
+}
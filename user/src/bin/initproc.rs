@@ -3,27 +3,37 @@
 
 extern crate user_lib;
 
-use user_lib::{exec, fork, wait, yield_};
+use user_lib::{
+    exec, fork, nanosleep, sigaction, sigreturn, waitpid_options, SignalAction, TimeSpec, SIGCHLD,
+    WNOHANG,
+};
+
+/// Release the zombie children, including the orphans given to us.
+fn reap_children() {
+    let mut exit_code: i32 = 0;
+    while waitpid_options(-1, &mut exit_code, WNOHANG) > 0 {
+        /*
+        println!(
+            "[initproc] Released a zombie process, exit_code={}",
+            exit_code,
+        );
+        */
+    }
+    sigreturn();
+}
 
 #[no_mangle]
 fn main() -> i32 {
+    // before the shell starts, so that no exit goes unnoticed
+    let mut action = SignalAction::default();
+    action.handler = reap_children as usize;
+    sigaction(SIGCHLD, Some(&action), None);
     if fork() == 0 {
         exec("user_shell\0", &[core::ptr::null::<u8>()]);
     } else {
         loop {
-            let mut exit_code: i32 = 0;
-            let pid = wait(&mut exit_code);
-            if pid == -1 {
-                yield_();
-                continue;
-            }
-            /*
-            println!(
-                "[initproc] Released a zombie process, pid={}, exit_code={}",
-                pid,
-                exit_code,
-            );
-            */
+            // SIGCHLD interrupts the sleep to run the handler
+            nanosleep(&TimeSpec { sec: 3600, nsec: 0 }, None);
         }
     }
     0
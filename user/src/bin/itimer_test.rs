@@ -0,0 +1,131 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::errno::{check, Errno};
+use user_lib::*;
+
+static ALRM_COUNT: AtomicUsize = AtomicUsize::new(0);
+static VTALRM_COUNT: AtomicUsize = AtomicUsize::new(0);
+static PROF_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+fn on_alrm() {
+    ALRM_COUNT.fetch_add(1, Ordering::SeqCst);
+}
+
+fn on_vtalrm() {
+    VTALRM_COUNT.fetch_add(1, Ordering::SeqCst);
+}
+
+fn on_prof() {
+    PROF_COUNT.fetch_add(1, Ordering::SeqCst);
+}
+
+fn set_handler(signum: i32, handler: usize) {
+    let mut action = SignalAction::default();
+    action.handler = handler;
+    assert_eq!(sigaction(signum, Some(&action), None), 0);
+}
+
+fn timer(value_ms: usize, interval_ms: usize) -> ITimerVal {
+    ITimerVal {
+        interval: TimeVal::from_ms(interval_ms),
+        value: TimeVal::from_ms(value_ms),
+    }
+}
+
+fn disarmed(which: usize) -> bool {
+    let mut value = timer(1, 1);
+    assert_eq!(getitimer(which, &mut value), 0);
+    value.value.to_us() == 0 && value.interval.to_us() == 0
+}
+
+/// Spin in user mode until `count` is increased, and return how long it took.
+fn spin_until(count: &AtomicUsize) -> isize {
+    let start = get_time();
+    while count.load(Ordering::SeqCst) == 0 {}
+    get_time() - start
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // disarmed at first
+    assert!(disarmed(ITIMER_REAL));
+    assert!(disarmed(ITIMER_VIRTUAL));
+    assert!(disarmed(ITIMER_PROF));
+    let mut value = ITimerVal::default();
+    assert_eq!(check(getitimer(3, &mut value)), Err(Errno::EINVAL));
+    value.value.usec = 1_000_000;
+    assert_eq!(
+        check(setitimer(ITIMER_REAL, &value, None)),
+        Err(Errno::EINVAL)
+    );
+
+    // alarm returns the time left before the previous one
+    assert_eq!(alarm(2), 0);
+    assert!(!disarmed(ITIMER_REAL));
+    assert_eq!(alarm(0), 2);
+    assert!(disarmed(ITIMER_REAL));
+
+    // a one-shot real timer
+    set_handler(SIGALRM, on_alrm as usize);
+    let start = get_time();
+    assert_eq!(setitimer(ITIMER_REAL, &timer(50, 0), None), 0);
+    assert_eq!(check(pause()), Err(Errno::EINTR));
+    assert!(get_time() - start >= 50);
+    assert_eq!(ALRM_COUNT.load(Ordering::SeqCst), 1);
+    assert!(disarmed(ITIMER_REAL));
+
+    // which goes on with an interval
+    assert_eq!(setitimer(ITIMER_REAL, &timer(20, 20), None), 0);
+    while ALRM_COUNT.load(Ordering::SeqCst) < 4 {
+        pause();
+    }
+    let mut old = ITimerVal::default();
+    assert_eq!(
+        setitimer(ITIMER_REAL, &ITimerVal::default(), Some(&mut old)),
+        0
+    );
+    assert_eq!(old.interval.to_us(), 20_000);
+    assert!(old.value.to_us() <= 20_000);
+    assert!(disarmed(ITIMER_REAL));
+
+    // the virtual and profiling timers count the time used
+    set_handler(SIGVTALRM, on_vtalrm as usize);
+    assert_eq!(setitimer(ITIMER_VIRTUAL, &timer(30, 0), None), 0);
+    assert!(spin_until(&VTALRM_COUNT) >= 30);
+    assert!(disarmed(ITIMER_VIRTUAL));
+    set_handler(SIGPROF, on_prof as usize);
+    assert_eq!(setitimer(ITIMER_PROF, &timer(30, 0), None), 0);
+    assert!(spin_until(&PROF_COUNT) >= 30);
+    assert!(disarmed(ITIMER_PROF));
+
+    // but not while sleeping
+    assert_eq!(setitimer(ITIMER_VIRTUAL, &timer(1000, 0), None), 0);
+    sleep(50);
+    assert_eq!(getitimer(ITIMER_VIRTUAL, &mut value), 0);
+    assert!(value.value.to_us() > 950_000);
+    assert_eq!(setitimer(ITIMER_VIRTUAL, &ITimerVal::default(), None), 0);
+
+    // the timers are not inherited, and SIGALRM terminates by default
+    assert_eq!(alarm(10), 0);
+    let pid = fork();
+    if pid == 0 {
+        assert!(disarmed(ITIMER_REAL));
+        set_handler(SIGALRM, SIG_DFL);
+        assert_eq!(setitimer(ITIMER_REAL, &timer(10, 0), None), 0);
+        loop {
+            pause();
+        }
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -SIGALRM);
+    assert_eq!(alarm(0), 10);
+
+    println!("itimer_test passed!");
+    0
+}
@@ -0,0 +1,125 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{check, Errno};
+use user_lib::*;
+
+fn wait_stop(pid: usize) -> i32 {
+    let mut status = 0;
+    assert_eq!(
+        waitpid_options(pid as isize, &mut status, WUNTRACED),
+        pid as isize
+    );
+    assert!(wifstopped(status));
+    wstopsig(status)
+}
+
+fn wait_exit(pid: usize) -> i32 {
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid as isize);
+    exit_code
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = getpid() as usize;
+    let sid = getsid(0);
+    assert!(sid > 0);
+    assert_eq!(check(setpgid(99999, 0)), Err(Errno::ESRCH));
+    assert_eq!(check(setpgid(0, 99999)), Err(Errno::EPERM));
+    assert_eq!(check(killpg(99999, SIGUSR1)), Err(Errno::ESRCH));
+    assert_eq!(check(tcsetpgrp(0, 99999)), Err(Errno::EPERM));
+
+    // lead a group of our own
+    assert_eq!(setpgid(0, 0), 0);
+    assert_eq!(getpgid(0), pid as isize);
+    assert_eq!(check(setsid()), Err(Errno::EPERM));
+
+    // a child starts a session and leaves our reach
+    let child = fork() as usize;
+    if child == 0 {
+        assert_eq!(setsid(), getpid());
+        assert_eq!(getsid(0), getpid());
+        assert_eq!(getpgid(0), getpid());
+        exit(0);
+    }
+    assert_eq!(wait_exit(child), 0);
+
+    // kill a whole group at once
+    let first = fork() as usize;
+    if first == 0 {
+        loop {
+            sleep(10);
+        }
+    }
+    assert_eq!(setpgid(first, 0), 0);
+    let second = fork() as usize;
+    if second == 0 {
+        loop {
+            sleep(10);
+        }
+    }
+    assert_eq!(setpgid(second, first), 0);
+    assert_eq!(getpgid(second), first as isize);
+    assert_eq!(killpg(first, SIGKILL), 0);
+    assert_eq!(wait_exit(first), -9);
+    assert_eq!(wait_exit(second), -9);
+
+    // wait for the children of our group, or of another one
+    let other = fork() as usize;
+    if other == 0 {
+        sleep(20);
+        exit(1);
+    }
+    assert_eq!(setpgid(other, 0), 0);
+    let same = fork() as usize;
+    if same == 0 {
+        sleep(20);
+        exit(2);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid_options(0, &mut exit_code, 0), same as isize);
+    assert_eq!(exit_code, 2);
+    assert_eq!(
+        check(waitpid_options(0, &mut exit_code, 0)),
+        Err(Errno::ECHILD)
+    );
+    assert_eq!(
+        waitpid_options(-(other as isize), &mut exit_code, 0),
+        other as isize
+    );
+    assert_eq!(exit_code, 1);
+
+    // stop and continue
+    let child = fork() as usize;
+    if child == 0 {
+        let end = get_time() + 100;
+        while get_time() < end {
+            yield_();
+        }
+        exit(3);
+    }
+    assert_eq!(kill(child, SIGTSTP), 0);
+    assert_eq!(wait_stop(child), SIGTSTP);
+    assert_eq!(kill(child, SIGCONT), 0);
+    assert_eq!(wait_exit(child), 3);
+
+    // reading the console out of the foreground group stops the reader
+    if tcgetpgrp(0) > 0 {
+        let child = fork() as usize;
+        if child == 0 {
+            assert_eq!(setpgid(0, 0), 0);
+            let mut buf = [0u8; 1];
+            read(0, &mut buf);
+            exit(0);
+        }
+        assert_eq!(wait_stop(child), SIGTTIN);
+        assert_eq!(kill(child, SIGKILL), 0);
+        assert_eq!(wait_exit(child), -9);
+    }
+    println!("job_control passed!");
+    0
+}
@@ -0,0 +1,26 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap, mprotect, MmapProt};
+
+const PAGE_SIZE: usize = 0x1000;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("Into Test mmap_protect, we will write to a read-only mapping...");
+    println!("Kernel should kill this application!");
+    let start = mmap(0, PAGE_SIZE, MmapProt::READ | MmapProt::WRITE);
+    assert!(start > 0);
+    let ptr = start as *mut u8;
+    unsafe {
+        ptr.write_volatile(1);
+    }
+    assert_eq!(mprotect(start as usize, PAGE_SIZE, MmapProt::READ), 0);
+    unsafe {
+        ptr.write_volatile(2);
+    }
+    0
+}
@@ -0,0 +1,70 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{check, Errno};
+use user_lib::{fork, mmap, mprotect, munmap, waitpid, MmapProt};
+
+const PAGE_SIZE: usize = 0x1000;
+const PAGES: usize = 4;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let start = mmap(0, PAGES * PAGE_SIZE, MmapProt::READ | MmapProt::WRITE);
+    assert!(start > 0);
+    let start = start as usize;
+    let buf = unsafe { core::slice::from_raw_parts_mut(start as *mut u8, PAGES * PAGE_SIZE) };
+    assert!(buf.iter().all(|byte| *byte == 0));
+    for (i, byte) in buf.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    // a fixed mapping must not overlap an existing one
+    assert_eq!(
+        check(mmap(start, PAGE_SIZE, MmapProt::READ)),
+        Err(Errno::ENOMEM)
+    );
+    // nor reach page 0, the sigreturn trampoline or the kernel half
+    let prot = MmapProt::READ;
+    assert_eq!(check(mmap(PAGE_SIZE, PAGE_SIZE, prot)), Err(Errno::EINVAL));
+    let last_page = usize::MAX & !(PAGE_SIZE - 1);
+    assert_eq!(
+        check(mmap(last_page, 2 * PAGE_SIZE, prot)),
+        Err(Errno::EINVAL)
+    );
+    assert_eq!(
+        check(mmap(0xffff_ffc0_0000_0000, PAGE_SIZE, prot)),
+        Err(Errno::EINVAL)
+    );
+    assert_eq!(check(mmap(0, usize::MAX, prot)), Err(Errno::ENOMEM));
+    // the child gets its own copy of the mapping
+    let pid = fork();
+    if pid == 0 {
+        buf[0] = 0xff;
+        return 0;
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(buf[0], 0);
+    // punch a hole in the middle of the area
+    assert_eq!(munmap(start + PAGE_SIZE, PAGE_SIZE), 0);
+    assert_eq!(
+        check(mprotect(start + PAGE_SIZE, PAGE_SIZE, MmapProt::READ)),
+        Err(Errno::ENOMEM)
+    );
+    assert_eq!(mprotect(start, PAGE_SIZE, MmapProt::READ), 0);
+    // ranges which wrap around or leave the user space are rejected
+    assert_eq!(check(munmap(start, usize::MAX)), Err(Errno::EINVAL));
+    assert_eq!(
+        check(mprotect(start, usize::MAX - PAGE_SIZE, prot)),
+        Err(Errno::EINVAL)
+    );
+    assert_eq!(check(munmap(last_page, PAGE_SIZE)), Err(Errno::EINVAL));
+    for i in [0, 2 * PAGE_SIZE + 1, PAGES * PAGE_SIZE - 1] {
+        assert_eq!(buf[i], i as u8);
+    }
+    assert_eq!(munmap(start, PAGES * PAGE_SIZE), 0);
+    println!("mmap_test passed!");
+    0
+}
@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{check, Errno};
+use user_lib::*;
+
+fn func() {
+    sigreturn();
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // nsec must be less than a second
+    let bad = TimeSpec {
+        sec: 0,
+        nsec: 1_000_000_000,
+    };
+    assert_eq!(check(nanosleep(&bad, None)), Err(Errno::EINVAL));
+
+    let start = get_time();
+    assert_eq!(nanosleep(&TimeSpec::from_ms(100), None), 0);
+    assert!(get_time() - start >= 100);
+
+    let deadline = get_time() as usize + 100;
+    let req = TimeSpec::from_ms(deadline);
+    assert_eq!(
+        clock_nanosleep(CLOCK_MONOTONIC, TIMER_ABSTIME, &req, None),
+        0
+    );
+    assert!(get_time() as usize >= deadline);
+
+    // a signal cuts the sleep short and reports the time left
+    let pid = fork();
+    if pid == 0 {
+        let mut new = SignalAction::default();
+        let mut old = SignalAction::default();
+        new.handler = func as usize;
+        assert_eq!(sigaction(SIGUSR1, Some(&new), Some(&mut old)), 0);
+        let mut rem = TimeSpec::default();
+        let ret = nanosleep(&TimeSpec::from_ms(5000), Some(&mut rem));
+        assert_eq!(check(ret), Err(Errno::EINTR));
+        assert!(rem.sec > 0 || rem.nsec > 0);
+        exit(0);
+    }
+    sleep(100);
+    assert_eq!(kill(pid as usize, SIGUSR1), 0);
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    println!("nanosleep_test passed!");
+    0
+}
@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{check, Errno};
+use user_lib::*;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(getpriority(PRIO_PROCESS, 0), 20);
+    assert_eq!(setpriority(PRIO_PROCESS, 0, 5), 0);
+    assert_eq!(getpriority(PRIO_PROCESS, 0), 15);
+    // out of range values are clamped
+    assert_eq!(setpriority(PRIO_PROCESS, 0, 100), 0);
+    assert_eq!(getpriority(PRIO_PROCESS, 0), 1);
+    assert_eq!(setpriority(PRIO_PROCESS, 0, -100), 0);
+    assert_eq!(getpriority(PRIO_PROCESS, 0), 40);
+    assert_eq!(check(setpriority(1, 0, 0)), Err(Errno::EINVAL));
+    assert_eq!(check(getpriority(PRIO_PROCESS, 99999)), Err(Errno::ESRCH));
+    assert_eq!(setpriority(PRIO_PROCESS, 0, 0), 0);
+
+    assert_eq!(sched_getscheduler(0), SCHED_OTHER as isize);
+    assert_eq!(check(sched_setscheduler(0, 1, 0)), Err(Errno::EINVAL));
+    assert_eq!(
+        check(sched_setscheduler(0, SCHED_BATCH, 1)),
+        Err(Errno::EINVAL)
+    );
+
+    // the child inherits the policy and nice value
+    assert_eq!(sched_setscheduler(0, SCHED_BATCH, 0), 0);
+    assert_eq!(setpriority(PRIO_PROCESS, 0, 10), 0);
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(sched_getscheduler(0), SCHED_BATCH as isize);
+        assert_eq!(getpriority(PRIO_PROCESS, 0), 10);
+        // a task of the lowest weight still makes progress
+        assert_eq!(sched_setscheduler(0, SCHED_IDLE, 0), 0);
+        for _ in 0..10 {
+            yield_();
+        }
+        exit(0);
+    }
+    let mut exit_code = -1;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    println!("sched_priority passed!");
+    0
+}
@@ -0,0 +1,120 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::hint::black_box;
+use core::ptr::{addr_of, addr_of_mut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::errno::{check, Errno};
+use user_lib::*;
+
+const ALT_STACK_SIZE: usize = 16384;
+static mut ALT_STACK: [u8; ALT_STACK_SIZE] = [0; ALT_STACK_SIZE];
+static PAGE: AtomicUsize = AtomicUsize::new(0);
+
+fn alt_stack_range() -> (usize, usize) {
+    let bottom = addr_of!(ALT_STACK) as usize;
+    (bottom, bottom + ALT_STACK_SIZE)
+}
+
+#[allow(unconditional_recursion)]
+fn overflow(depth: usize) {
+    let frame = [depth; 16];
+    overflow(depth + 1);
+    black_box(&frame);
+}
+
+extern "C" fn on_overflow(_sig: i32, info: &SigInfo, _ucontext: &mut UContext) {
+    // the handler runs on the alternate stack, which can't change meanwhile
+    let local = 0;
+    let (bottom, top) = alt_stack_range();
+    let sp = addr_of!(local) as usize;
+    assert!(bottom <= sp && sp < top);
+    let mut stack = SignalStack::default();
+    assert_eq!(sigaltstack(None, Some(&mut stack)), 0);
+    assert_eq!(stack.flags, SS_ONSTACK);
+    assert_eq!(check(sigaltstack(Some(&stack), None)), Err(Errno::EPERM));
+    // below the stack, which is not mapped
+    assert_eq!(info.signo, SIGSEGV);
+    assert_eq!(info.code, SEGV_MAPERR);
+    assert!(info.addr() < sp);
+    assert_eq!(info.access(), MmapProt::WRITE);
+    exit(42);
+}
+
+extern "C" fn on_readonly(_sig: i32, info: &SigInfo, _ucontext: &mut UContext) {
+    let page = PAGE.load(Ordering::SeqCst);
+    assert_eq!(info.code, SEGV_ACCERR);
+    assert_eq!(info.addr(), page);
+    assert_eq!(info.access(), MmapProt::WRITE);
+    // the store is made again after the handler
+    assert_eq!(mprotect(page, 4096, MmapProt::READ | MmapProt::WRITE), 0);
+}
+
+fn set_handler(signum: i32, handler: usize, flags: u32) {
+    let mut action = SignalAction::default();
+    action.handler = handler;
+    action.flags = flags;
+    assert_eq!(sigaction(signum, Some(&action), None), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // disabled at first, and checked when it is set
+    let mut stack = SignalStack::default();
+    stack.flags = 0;
+    assert_eq!(sigaltstack(None, Some(&mut stack)), 0);
+    assert_eq!(stack.flags, SS_DISABLE);
+    let mut small = SignalStack {
+        sp: addr_of_mut!(ALT_STACK) as usize,
+        flags: 0,
+        size: MINSIGSTKSZ - 1,
+    };
+    assert_eq!(check(sigaltstack(Some(&small), None)), Err(Errno::ENOMEM));
+    small.size = ALT_STACK_SIZE;
+    small.flags = 4;
+    assert_eq!(check(sigaltstack(Some(&small), None)), Err(Errno::EINVAL));
+
+    // a stack overflow is caught on the alternate stack
+    let pid = fork();
+    if pid == 0 {
+        let (bottom, _) = alt_stack_range();
+        let stack = SignalStack {
+            sp: bottom,
+            flags: 0,
+            size: ALT_STACK_SIZE,
+        };
+        assert_eq!(sigaltstack(Some(&stack), None), 0);
+        set_handler(SIGSEGV, on_overflow as usize, SA_ONSTACK | SA_SIGINFO);
+        overflow(0);
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 42);
+
+    // but not without it, the frame doesn't fit on the stack
+    let pid = fork();
+    if pid == 0 {
+        set_handler(SIGSEGV, on_overflow as usize, SA_SIGINFO);
+        overflow(0);
+        exit(0);
+    }
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -SIGSEGV);
+
+    // a write to a read only page, which the handler makes writable
+    let page = mmap(0, 4096, MmapProt::READ);
+    assert!(page > 0);
+    PAGE.store(page as usize, Ordering::SeqCst);
+    set_handler(SIGSEGV, on_readonly as usize, SA_SIGINFO);
+    unsafe {
+        (page as *mut usize).write_volatile(7);
+        assert_eq!((page as *const usize).read_volatile(), 7);
+    }
+
+    println!("sig_altstack passed!");
+    0
+}
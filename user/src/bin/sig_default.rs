@@ -0,0 +1,124 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::*;
+
+static USR2_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+fn on_usr1() {
+    // SIGUSR1 and the mask of its action are blocked meanwhile
+    let mut mask = SignalFlags::empty();
+    sigprocmask(SIG_BLOCK, None, Some(&mut mask));
+    assert!(mask.contains(SignalFlags::SIGUSR1 | SignalFlags::SIGUSR2));
+    kill(getpid() as usize, SIGUSR2);
+    assert_eq!(USR2_COUNT.load(Ordering::SeqCst), 0);
+    sigreturn();
+}
+
+fn on_usr2() {
+    USR2_COUNT.fetch_add(1, Ordering::SeqCst);
+    sigreturn();
+}
+
+fn signal_mask() -> SignalFlags {
+    let mut mask = SignalFlags::empty();
+    sigprocmask(SIG_BLOCK, None, Some(&mut mask));
+    mask
+}
+
+fn set_handler(signum: i32, handler: usize, mask: SignalFlags) {
+    let mut action = SignalAction::default();
+    action.handler = handler;
+    action.mask = mask;
+    assert_eq!(sigaction(signum, Some(&action), None), 0);
+}
+
+/// Run `f` in a child and return its exit code.
+fn run(f: fn()) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        f();
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    exit_code
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // terminate by default
+    assert_eq!(
+        run(|| {
+            kill(getpid() as usize, SIGTERM);
+        }),
+        -SIGTERM
+    );
+    assert_eq!(
+        run(|| {
+            kill(getpid() as usize, SIGUSR1);
+        }),
+        -SIGUSR1
+    );
+    assert_eq!(
+        run(|| {
+            kill(getpid() as usize, SIGALRM);
+        }),
+        -SIGALRM
+    );
+    // ignore by default
+    assert_eq!(
+        run(|| {
+            kill(getpid() as usize, SIGWINCH);
+            kill(getpid() as usize, SIGURG);
+            kill(getpid() as usize, SIGCHLD);
+            kill(getpid() as usize, SIGCONT);
+        }),
+        0
+    );
+    // SIG_IGN discards and SIG_DFL restores the default
+    assert_eq!(
+        run(|| {
+            set_handler(SIGTERM, SIG_IGN, SignalFlags::empty());
+            kill(getpid() as usize, SIGTERM);
+        }),
+        0
+    );
+    assert_eq!(
+        run(|| {
+            set_handler(SIGTERM, on_usr2 as usize, SignalFlags::empty());
+            set_handler(SIGTERM, SIG_DFL, SignalFlags::empty());
+            kill(getpid() as usize, SIGTERM);
+        }),
+        -SIGTERM
+    );
+    // a blocked signal is delivered once unblocked
+    assert_eq!(
+        run(|| {
+            sigprocmask(SIG_BLOCK, Some(&SignalFlags::SIGTERM), None);
+            kill(getpid() as usize, SIGTERM);
+            sigprocmask(SIG_UNBLOCK, Some(&SignalFlags::SIGTERM), None);
+            exit(1);
+        }),
+        -SIGTERM
+    );
+    // SIGKILL and SIGSTOP can't be blocked
+    let unblockable = SignalFlags::SIGKILL | SignalFlags::SIGSTOP;
+    sigprocmask(SIG_SETMASK, Some(&unblockable), None);
+    assert_eq!(signal_mask(), SignalFlags::empty());
+
+    // the mask of the action applies while the handler runs
+    set_handler(SIGUSR1, on_usr1 as usize, SignalFlags::SIGUSR2);
+    set_handler(SIGUSR2, on_usr2 as usize, SignalFlags::empty());
+    kill(getpid() as usize, SIGUSR1);
+    assert_eq!(USR2_COUNT.load(Ordering::SeqCst), 1);
+    assert_eq!(signal_mask(), SignalFlags::empty());
+    set_handler(SIGUSR1, SIG_DFL, SignalFlags::empty());
+    set_handler(SIGUSR2, SIG_DFL, SignalFlags::empty());
+    println!("sig_default passed!");
+    0
+}
@@ -0,0 +1,127 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::errno::{check, Errno};
+use user_lib::*;
+
+static STEP: AtomicUsize = AtomicUsize::new(0);
+static USR1_AT: AtomicUsize = AtomicUsize::new(0);
+static USR2_AT: AtomicUsize = AtomicUsize::new(0);
+static USR1_DONE_AT: AtomicUsize = AtomicUsize::new(0);
+static DEPTH: AtomicUsize = AtomicUsize::new(0);
+static MAX_DEPTH: AtomicUsize = AtomicUsize::new(0);
+static SENDER: AtomicUsize = AtomicUsize::new(0);
+
+fn step() -> usize {
+    STEP.fetch_add(1, Ordering::SeqCst) + 1
+}
+
+fn signal_mask() -> SignalFlags {
+    let mut mask = SignalFlags::empty();
+    sigprocmask(SIG_BLOCK, None, Some(&mut mask));
+    mask
+}
+
+fn set_handler(signum: i32, handler: usize, flags: u32) {
+    let mut action = SignalAction::default();
+    action.handler = handler;
+    action.flags = flags;
+    assert_eq!(sigaction(signum, Some(&action), None), 0);
+}
+
+// the handlers return without sigreturn
+
+fn on_usr1() {
+    USR1_AT.store(step(), Ordering::SeqCst);
+    // SIGUSR2 is not blocked, so its handler runs on top of this one
+    kill(getpid() as usize, SIGUSR2);
+    USR1_DONE_AT.store(step(), Ordering::SeqCst);
+}
+
+fn on_usr2() {
+    USR2_AT.store(step(), Ordering::SeqCst);
+}
+
+fn on_usr1_nodefer() {
+    let depth = DEPTH.fetch_add(1, Ordering::SeqCst) + 1;
+    MAX_DEPTH.fetch_max(depth, Ordering::SeqCst);
+    if depth == 1 {
+        kill(getpid() as usize, SIGUSR1);
+    }
+    DEPTH.fetch_sub(1, Ordering::SeqCst);
+}
+
+extern "C" fn on_usr1_info(sig: i32, info: &SigInfo, ucontext: &mut UContext) {
+    assert_eq!(sig, SIGUSR1);
+    assert_eq!(info.signo, SIGUSR1);
+    assert_eq!(info.code, SI_USER);
+    SENDER.store(info.pid(), Ordering::SeqCst);
+    // nothing was blocked before, and SIGUSR2 is after the handler
+    assert_eq!(ucontext.sigmask, 0);
+    ucontext.sigmask |= SignalFlags::SIGUSR2.bits();
+}
+
+fn on_usr1_empty() {}
+
+/// Wait for a child which sends SIGUSR1 to us in the middle of the wait.
+fn interrupted_waitpid() -> (isize, isize) {
+    let parent = getpid() as usize;
+    let pid = fork();
+    if pid == 0 {
+        sleep(50);
+        kill(parent, SIGUSR1);
+        sleep(50);
+        exit(7);
+    }
+    let mut exit_code = 0;
+    (pid, waitpid(pid as usize, &mut exit_code))
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // the handlers return to the trampoline, and nest
+    set_handler(SIGUSR1, on_usr1 as usize, 0);
+    set_handler(SIGUSR2, on_usr2 as usize, 0);
+    let local = 42;
+    assert_eq!(kill(getpid() as usize, SIGUSR1), 0);
+    assert_eq!(local, 42);
+    assert_eq!(USR1_AT.load(Ordering::SeqCst), 1);
+    assert_eq!(USR2_AT.load(Ordering::SeqCst), 2);
+    assert_eq!(USR1_DONE_AT.load(Ordering::SeqCst), 3);
+    assert_eq!(signal_mask(), SignalFlags::empty());
+    // sigreturn out of a handler is an error
+    assert_eq!(check(sigreturn()), Err(Errno::EINVAL));
+
+    // SA_NODEFER lets the handler interrupt itself
+    set_handler(SIGUSR1, on_usr1_nodefer as usize, SA_NODEFER);
+    kill(getpid() as usize, SIGUSR1);
+    assert_eq!(MAX_DEPTH.load(Ordering::SeqCst), 2);
+    assert_eq!(DEPTH.load(Ordering::SeqCst), 0);
+
+    // SA_SIGINFO passes the siginfo and the ucontext, whose mask is restored
+    set_handler(SIGUSR1, on_usr1_info as usize, SA_SIGINFO);
+    kill(getpid() as usize, SIGUSR1);
+    assert_eq!(SENDER.load(Ordering::SeqCst), getpid() as usize);
+    assert_eq!(signal_mask(), SignalFlags::SIGUSR2);
+    sigprocmask(SIG_UNBLOCK, Some(&SignalFlags::SIGUSR2), None);
+
+    // a wait interrupted by a handler fails with EINTR
+    set_handler(SIGUSR1, on_usr1_empty as usize, 0);
+    let (pid, ret) = interrupted_waitpid();
+    assert_eq!(check(ret), Err(Errno::EINTR));
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 7);
+
+    // unless the handler has SA_RESTART
+    set_handler(SIGUSR1, on_usr1_empty as usize, SA_RESTART);
+    let (pid, ret) = interrupted_waitpid();
+    assert_eq!(ret, pid);
+
+    println!("sig_frame passed!");
+    0
+}
@@ -0,0 +1,124 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::errno::{check, Errno};
+use user_lib::*;
+
+const LOG_SIZE: usize = 32;
+const ZERO: AtomicUsize = AtomicUsize::new(0);
+// the signals handled, with their values and senders
+static SIGNOS: [AtomicUsize; LOG_SIZE] = [ZERO; LOG_SIZE];
+static VALUES: [AtomicUsize; LOG_SIZE] = [ZERO; LOG_SIZE];
+static PIDS: [AtomicUsize; LOG_SIZE] = [ZERO; LOG_SIZE];
+static CODES: [AtomicUsize; LOG_SIZE] = [ZERO; LOG_SIZE];
+static HANDLED: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn on_signal(sig: i32, info: &SigInfo, _ucontext: &mut UContext) {
+    assert_eq!(info.signo, sig);
+    let i = HANDLED.fetch_add(1, Ordering::SeqCst);
+    if i < LOG_SIZE {
+        SIGNOS[i].store(sig as usize, Ordering::SeqCst);
+        VALUES[i].store(info.value(), Ordering::SeqCst);
+        PIDS[i].store(info.pid(), Ordering::SeqCst);
+        CODES[i].store(info.code as usize, Ordering::SeqCst);
+    }
+}
+
+fn flag(signum: i32) -> SignalFlags {
+    SignalFlags::from_bits(1 << signum).unwrap()
+}
+
+fn set_handler(signum: i32, mask: SignalFlags) {
+    let mut action = SignalAction::default();
+    action.handler = on_signal as usize;
+    action.mask = mask;
+    action.flags = SA_SIGINFO;
+    assert_eq!(sigaction(signum, Some(&action), None), 0);
+}
+
+fn check_log(start: usize, signum: i32, value: usize, pid: usize, code: i32) {
+    assert_eq!(SIGNOS[start].load(Ordering::SeqCst), signum as usize);
+    assert_eq!(PIDS[start].load(Ordering::SeqCst), pid);
+    assert_eq!(CODES[start].load(Ordering::SeqCst), code as usize);
+    if code == SI_QUEUE {
+        assert_eq!(VALUES[start].load(Ordering::SeqCst), value);
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = getpid() as usize;
+    // the handlers don't nest, so they run in the order of the signals
+    let signals = flag(SIGUSR1) | flag(SIGRTMIN) | flag(SIGRTMIN + 1);
+    set_handler(SIGUSR1, signals);
+    set_handler(SIGRTMIN, signals);
+    set_handler(SIGRTMIN + 1, signals);
+
+    // real-time signals are queued, standard ones are merged
+    sigprocmask(SIG_BLOCK, Some(&signals), None);
+    for value in 1..=3 {
+        assert_eq!(sigqueue(pid, SIGRTMIN, value), 0);
+    }
+    assert_eq!(sigqueue(pid, SIGRTMIN + 1, 10), 0);
+    assert_eq!(kill(pid, SIGUSR1), 0);
+    assert_eq!(kill(pid, SIGUSR1), 0);
+    assert_eq!(HANDLED.load(Ordering::SeqCst), 0);
+    sigprocmask(SIG_SETMASK, Some(&SignalFlags::empty()), None);
+    assert_eq!(HANDLED.load(Ordering::SeqCst), 5);
+    check_log(0, SIGUSR1, 0, pid, SI_USER);
+    check_log(1, SIGRTMIN, 1, pid, SI_QUEUE);
+    check_log(2, SIGRTMIN, 2, pid, SI_QUEUE);
+    check_log(3, SIGRTMIN, 3, pid, SI_QUEUE);
+    check_log(4, SIGRTMIN + 1, 10, pid, SI_QUEUE);
+
+    // none is lost between processes
+    HANDLED.store(0, Ordering::SeqCst);
+    sigprocmask(SIG_BLOCK, Some(&flag(SIGRTMIN)), None);
+    let child = fork();
+    if child == 0 {
+        for value in 0..20 {
+            assert_eq!(sigqueue(pid, SIGRTMIN, 100 + value), 0);
+        }
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(child as usize, &mut exit_code), child);
+    assert_eq!(exit_code, 0);
+    sigprocmask(SIG_SETMASK, Some(&SignalFlags::empty()), None);
+    assert_eq!(HANDLED.load(Ordering::SeqCst), 20);
+    for i in 0..20 {
+        check_log(i, SIGRTMIN, 100 + i, child as usize, SI_QUEUE);
+    }
+
+    // until the queue is full
+    HANDLED.store(0, Ordering::SeqCst);
+    sigprocmask(SIG_BLOCK, Some(&flag(SIGRTMIN)), None);
+    let mut queued = 0;
+    let ret = loop {
+        let ret = sigqueue(pid, SIGRTMIN, queued);
+        if ret < 0 {
+            break ret;
+        }
+        queued += 1;
+    };
+    assert_eq!(check(ret), Err(Errno::EAGAIN));
+    assert!(queued >= LOG_SIZE);
+    sigprocmask(SIG_SETMASK, Some(&SignalFlags::empty()), None);
+    assert_eq!(HANDLED.load(Ordering::SeqCst), queued);
+    for i in 0..LOG_SIZE {
+        check_log(i, SIGRTMIN, i, pid, SI_QUEUE);
+    }
+
+    assert_eq!(check(sigqueue(pid, SIGRTMAX + 1, 0)), Err(Errno::EINVAL));
+    assert_eq!(
+        check(sigqueue(usize::MAX >> 1, SIGRTMIN, 0)),
+        Err(Errno::ESRCH)
+    );
+
+    println!("sig_queue passed!");
+    0
+}
@@ -73,8 +73,19 @@ fn user_sig_test_restore() {
 }
 
 fn kernel_sig_test_ignore() {
-    sigprocmask(SignalFlags::SIGSTOP.bits() as u32);
-    if kill(getpid() as usize, SignalFlags::SIGSTOP.bits()) < 0 {
+    // an ignored signal is discarded
+    let mut new = SignalAction::default();
+    new.handler = SIG_IGN;
+    if sigaction(SIGUSR1, Some(&new), None) < 0 {
+        panic!("Sigaction failed!");
+    }
+    if kill(getpid() as usize, SIGUSR1) < 0 {
+        println!("kill faild\n");
+        exit(-1);
+    }
+    // and a blocked one stays pending
+    sigprocmask(SIG_BLOCK, Some(&SignalFlags::SIGUSR2), None);
+    if kill(getpid() as usize, SIGUSR2) < 0 {
         println!("kill faild\n");
         exit(-1);
     }
@@ -128,7 +139,9 @@ fn final_sig_test() {
         }
         write(pipe_fd[1], &[0u8]);
         close(pipe_fd[1]);
-        loop {yield_();}
+        loop {
+            pause();
+        }
     } else {
         close(pipe_fd[1]);
         let mut buf = [0u8; 1];
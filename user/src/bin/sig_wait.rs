@@ -0,0 +1,145 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::errno::{check, Errno};
+use user_lib::*;
+
+static USR1_COUNT: AtomicUsize = AtomicUsize::new(0);
+// the signal mask while the handler runs
+static USR1_MASK: AtomicUsize = AtomicUsize::new(0);
+
+fn on_usr1() {
+    USR1_COUNT.fetch_add(1, Ordering::SeqCst);
+    USR1_MASK.store(signal_mask().bits() as usize, Ordering::SeqCst);
+}
+
+fn signal_mask() -> SignalFlags {
+    let mut mask = SignalFlags::empty();
+    sigprocmask(SIG_BLOCK, None, Some(&mut mask));
+    mask
+}
+
+fn set_handler(signum: i32, handler: usize) {
+    let mut action = SignalAction::default();
+    action.handler = handler;
+    assert_eq!(sigaction(signum, Some(&action), None), 0);
+}
+
+/// Fork a child which sends `signum` to us after a while.
+fn send_later(signum: i32) -> usize {
+    let parent = getpid() as usize;
+    let pid = fork();
+    if pid == 0 {
+        sleep(50);
+        kill(parent, signum);
+        exit(0);
+    }
+    pid as usize
+}
+
+fn reap(pid: usize) {
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), pid as isize);
+    assert_eq!(exit_code, 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let usr1 = SignalFlags::SIGUSR1;
+    let usr2 = SignalFlags::SIGUSR2;
+    let empty = SignalFlags::empty();
+
+    // the mask is added to, removed from or replaced
+    let mut old = SignalFlags::all();
+    assert_eq!(sigprocmask(SIG_BLOCK, Some(&usr1), Some(&mut old)), 0);
+    assert_eq!(old, empty);
+    assert_eq!(sigprocmask(SIG_BLOCK, Some(&usr2), Some(&mut old)), 0);
+    assert_eq!(old, usr1);
+    assert_eq!(sigprocmask(SIG_UNBLOCK, Some(&usr1), Some(&mut old)), 0);
+    assert_eq!(old, usr1 | usr2);
+    assert_eq!(sigprocmask(SIG_SETMASK, Some(&usr1), None), 0);
+    assert_eq!(signal_mask(), usr1);
+    assert_eq!(check(sigprocmask(3, Some(&usr2), None)), Err(Errno::EINVAL));
+    assert_eq!(signal_mask(), usr1);
+
+    // a blocked signal is pending until it is unblocked
+    set_handler(SIGUSR1, on_usr1 as usize);
+    kill(getpid() as usize, SIGUSR1);
+    let mut pending = empty;
+    assert_eq!(sigpending(&mut pending), 0);
+    assert_eq!(pending, usr1);
+    assert_eq!(USR1_COUNT.load(Ordering::SeqCst), 0);
+    sigprocmask(SIG_UNBLOCK, Some(&usr1), None);
+    assert_eq!(USR1_COUNT.load(Ordering::SeqCst), 1);
+    assert_eq!(sigpending(&mut pending), 0);
+    assert_eq!(pending, empty);
+
+    // sigsuspend unblocks it only until the handler has run
+    sigprocmask(SIG_SETMASK, Some(&usr1), None);
+    let pid = send_later(SIGUSR1);
+    assert_eq!(check(sigsuspend(&usr2)), Err(Errno::EINTR));
+    assert_eq!(USR1_COUNT.load(Ordering::SeqCst), 2);
+    assert_eq!(
+        USR1_MASK.load(Ordering::SeqCst) as u64,
+        (usr1 | usr2).bits()
+    );
+    assert_eq!(signal_mask(), usr1);
+    reap(pid);
+
+    // so does pause, without changing the mask
+    sigprocmask(SIG_SETMASK, Some(&empty), None);
+    let pid = send_later(SIGUSR1);
+    assert_eq!(check(pause()), Err(Errno::EINTR));
+    assert_eq!(USR1_COUNT.load(Ordering::SeqCst), 3);
+    reap(pid);
+
+    // sigtimedwait takes a blocked signal without its handler
+    let rtmin = SignalFlags::from_bits(1 << SIGRTMIN).unwrap();
+    sigprocmask(SIG_BLOCK, Some(&rtmin), None);
+    set_handler(SIGRTMIN, on_usr1 as usize);
+    assert_eq!(sigqueue(getpid() as usize, SIGRTMIN, 7), 0);
+    let mut info = SigInfo::default();
+    assert_eq!(
+        sigtimedwait(&rtmin, Some(&mut info), None),
+        SIGRTMIN as isize
+    );
+    assert_eq!(info.signo, SIGRTMIN);
+    assert_eq!(info.code, SI_QUEUE);
+    assert_eq!(info.value(), 7);
+    assert_eq!(USR1_COUNT.load(Ordering::SeqCst), 3);
+
+    // and waits for it
+    sigprocmask(SIG_BLOCK, Some(&usr2), None);
+    let pid = send_later(SIGUSR2);
+    assert_eq!(sigtimedwait(&usr2, Some(&mut info), None), SIGUSR2 as isize);
+    assert_eq!(info.pid(), pid);
+    assert_eq!(info.code, SI_USER);
+    reap(pid);
+
+    // until the timeout
+    let timeout = TimeSpec::from_ms(50);
+    let start = get_time();
+    assert_eq!(
+        check(sigtimedwait(&usr2, None, Some(&timeout))),
+        Err(Errno::EAGAIN)
+    );
+    assert!(get_time() - start >= 50);
+    let timeout = TimeSpec::default();
+    assert_eq!(
+        check(sigtimedwait(&usr2, None, Some(&timeout))),
+        Err(Errno::EAGAIN)
+    );
+
+    // or until another signal runs its handler
+    let pid = send_later(SIGUSR1);
+    assert_eq!(check(sigtimedwait(&usr2, None, None)), Err(Errno::EINTR));
+    assert_eq!(USR1_COUNT.load(Ordering::SeqCst), 4);
+    reap(pid);
+
+    println!("sig_wait passed!");
+    0
+}
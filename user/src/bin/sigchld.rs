@@ -0,0 +1,82 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::errno::{check, Errno};
+use user_lib::*;
+
+static SIGCHLD_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+fn on_sigchld() {
+    SIGCHLD_COUNT.fetch_add(1, Ordering::SeqCst);
+    sigreturn();
+}
+
+fn set_sigchld(handler: usize) {
+    let mut action = SignalAction::default();
+    action.handler = handler;
+    assert_eq!(sigaction(SIGCHLD, Some(&action), None), 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    set_sigchld(on_sigchld as usize);
+
+    // an exit is signalled
+    let pid = fork();
+    if pid == 0 {
+        exit(3);
+    }
+    let mut status = 0;
+    assert_eq!(waitpid(pid as usize, &mut status), pid);
+    assert_eq!(status, 3);
+    assert_eq!(SIGCHLD_COUNT.load(Ordering::SeqCst), 1);
+
+    // and so are a stop and a continuation
+    let pid = fork();
+    if pid == 0 {
+        loop {
+            yield_();
+        }
+    }
+    assert_eq!(kill(pid as usize, SIGSTOP), 0);
+    assert_eq!(waitpid_options(pid, &mut status, WUNTRACED), pid);
+    assert!(wifstopped(status));
+    assert_eq!(SIGCHLD_COUNT.load(Ordering::SeqCst), 2);
+    assert_eq!(kill(pid as usize, SIGCONT), 0);
+    assert_eq!(waitpid_options(pid, &mut status, WCONTINUED), pid);
+    assert!(wifcontinued(status));
+    assert_eq!(SIGCHLD_COUNT.load(Ordering::SeqCst), 3);
+    assert_eq!(kill(pid as usize, SIGKILL), 0);
+    assert_eq!(waitpid(pid as usize, &mut status), pid);
+    assert_eq!(SIGCHLD_COUNT.load(Ordering::SeqCst), 4);
+
+    // SIGCHLD is ignored by default, the child is still waited for
+    set_sigchld(SIG_DFL);
+    let pid = fork();
+    if pid == 0 {
+        exit(5);
+    }
+    assert_eq!(waitpid(pid as usize, &mut status), pid);
+    assert_eq!(status, 5);
+
+    // a parent ignoring SIGCHLD leaves no zombie behind
+    set_sigchld(SIG_IGN);
+    let pid = fork();
+    if pid == 0 {
+        sleep(10);
+        exit(7);
+    }
+    assert_eq!(check(wait(&mut status)), Err(Errno::ECHILD));
+    assert_eq!(
+        check(waitpid_nb(pid as usize, &mut status)),
+        Err(Errno::ECHILD)
+    );
+    assert_eq!(SIGCHLD_COUNT.load(Ordering::SeqCst), 4);
+    set_sigchld(SIG_DFL);
+    println!("sigchld passed!");
+    0
+}
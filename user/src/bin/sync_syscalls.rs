@@ -0,0 +1,137 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use user_lib::errno::{check, Errno};
+use user_lib::*;
+
+const THREADS: usize = 4;
+const ROUNDS: usize = 200;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+static READY: AtomicBool = AtomicBool::new(false);
+static MUTEX: AtomicUsize = AtomicUsize::new(0);
+static OTHER: AtomicUsize = AtomicUsize::new(0);
+static SEMAPHORE: AtomicUsize = AtomicUsize::new(0);
+static CONDVAR: AtomicUsize = AtomicUsize::new(0);
+
+/// Increment the counter in two steps, which only works with the mutex.
+fn add(_arg: usize) -> ! {
+    let mutex = MUTEX.load(Ordering::SeqCst);
+    for i in 0..ROUNDS {
+        assert_eq!(mutex_lock(mutex), 0);
+        let value = COUNTER.load(Ordering::Relaxed);
+        if i % 10 == 0 {
+            yield_();
+        }
+        COUNTER.store(value + 1, Ordering::Relaxed);
+        assert_eq!(mutex_unlock(mutex), 0);
+    }
+    exit(0)
+}
+
+fn produce(_arg: usize) -> ! {
+    for _ in 0..ROUNDS {
+        COUNTER.fetch_add(1, Ordering::SeqCst);
+        assert_eq!(semaphore_up(SEMAPHORE.load(Ordering::SeqCst)), 0);
+    }
+    exit(0)
+}
+
+fn notify(_arg: usize) -> ! {
+    let mutex = MUTEX.load(Ordering::SeqCst);
+    sleep(10);
+    assert_eq!(mutex_lock(mutex), 0);
+    READY.store(true, Ordering::SeqCst);
+    assert_eq!(condvar_signal(CONDVAR.load(Ordering::SeqCst)), 0);
+    assert_eq!(mutex_unlock(mutex), 0);
+    exit(0)
+}
+
+/// Hold the other mutex and wait for the one the main thread holds.
+fn lock_both(_arg: usize) -> ! {
+    let mutex = MUTEX.load(Ordering::SeqCst);
+    let other = OTHER.load(Ordering::SeqCst);
+    assert_eq!(mutex_lock(other), 0);
+    READY.store(true, Ordering::SeqCst);
+    assert_eq!(mutex_lock(mutex), 0);
+    assert_eq!(mutex_unlock(mutex), 0);
+    assert_eq!(mutex_unlock(other), 0);
+    exit(0)
+}
+
+fn join(tid: usize) {
+    let mut exit_code = -1;
+    assert_eq!(waittid(tid, &mut exit_code), tid as isize);
+    assert_eq!(exit_code, 0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(check(mutex_lock(100)), Err(Errno::EINVAL));
+    assert_eq!(check(semaphore_up(100)), Err(Errno::EINVAL));
+    assert_eq!(check(condvar_signal(100)), Err(Errno::EINVAL));
+
+    let mutex = check(mutex_create()).unwrap();
+    MUTEX.store(mutex, Ordering::SeqCst);
+    assert_eq!(check(mutex_unlock(mutex)), Err(Errno::EPERM));
+    let tids: [usize; THREADS] =
+        core::array::from_fn(|_| check(thread_create(add as usize, 0)).unwrap());
+    for tid in tids {
+        join(tid);
+    }
+    assert_eq!(COUNTER.load(Ordering::SeqCst), THREADS * ROUNDS);
+    println!("mutex ok");
+
+    COUNTER.store(0, Ordering::SeqCst);
+    SEMAPHORE.store(check(semaphore_create(0)).unwrap(), Ordering::SeqCst);
+    let tid = check(thread_create(produce as usize, 0)).unwrap();
+    for consumed in 1..=ROUNDS {
+        assert_eq!(semaphore_down(SEMAPHORE.load(Ordering::SeqCst)), 0);
+        assert!(COUNTER.load(Ordering::SeqCst) >= consumed);
+    }
+    join(tid);
+    println!("semaphore ok");
+
+    CONDVAR.store(check(condvar_create()).unwrap(), Ordering::SeqCst);
+    let tid = check(thread_create(notify as usize, 0)).unwrap();
+    assert_eq!(mutex_lock(mutex), 0);
+    while !READY.load(Ordering::SeqCst) {
+        assert_eq!(condvar_wait(CONDVAR.load(Ordering::SeqCst), mutex), 0);
+    }
+    assert_eq!(mutex_unlock(mutex), 0);
+    join(tid);
+    println!("condvar ok");
+
+    assert_eq!(enable_deadlock_detect(true), 0);
+    // a second down of a semaphore with one unit waits for ourselves
+    let semaphore = check(semaphore_create(1)).unwrap();
+    assert_eq!(semaphore_down(semaphore), 0);
+    assert_eq!(check(semaphore_down(semaphore)), Err(Errno::EDEADLK));
+    assert_eq!(semaphore_up(semaphore), 0);
+    // each thread holds the mutex the other one waits for
+    READY.store(false, Ordering::SeqCst);
+    OTHER.store(check(mutex_create()).unwrap(), Ordering::SeqCst);
+    assert_eq!(mutex_lock(mutex), 0);
+    let tid = check(thread_create(lock_both as usize, 0)).unwrap();
+    while !READY.load(Ordering::SeqCst) {
+        yield_();
+    }
+    // let it block on the mutex
+    for _ in 0..100 {
+        yield_();
+    }
+    assert_eq!(
+        check(mutex_lock(OTHER.load(Ordering::SeqCst))),
+        Err(Errno::EDEADLK)
+    );
+    assert_eq!(mutex_unlock(mutex), 0);
+    join(tid);
+    assert_eq!(enable_deadlock_detect(false), 0);
+    println!("deadlock detection ok");
+    println!("sync_syscalls passed!");
+    0
+}
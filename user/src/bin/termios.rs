@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{check, Errno};
+use user_lib::*;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut saved = Termios::default();
+    assert_eq!(tcgetattr(0, &mut saved), 0);
+    assert_eq!(saved.lflag & (ISIG | ICANON | ECHO), ISIG | ICANON | ECHO);
+    assert_eq!(saved.cc[VINTR], 0x03);
+    assert_eq!(saved.cc[VSUSP], 0x1a);
+
+    // a raw console which doesn't wait for input
+    let mut raw = saved;
+    raw.lflag &= !(ICANON | ECHO);
+    raw.cc[VMIN] = 0;
+    assert_eq!(tcsetattr(0, TCSAFLUSH, &raw), 0);
+    let mut termios = Termios::default();
+    assert_eq!(tcgetattr(0, &mut termios), 0);
+    assert_eq!(termios.lflag, raw.lflag);
+    assert_eq!(termios.cc[VMIN], 0);
+    let mut buf = [0u8; 16];
+    assert_eq!(read(0, &mut buf), 0);
+
+    assert_eq!(tcsetattr(0, TCSANOW, &saved), 0);
+    assert_eq!(tcgetattr(0, &mut termios), 0);
+    assert_eq!(termios.lflag, saved.lflag);
+    assert_eq!(termios.cc[VMIN], saved.cc[VMIN]);
+    assert_eq!(check(tcsetattr(0, 3, &saved)), Err(Errno::EINVAL));
+
+    // a pipe is not a terminal
+    let mut pipe_fd = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fd), 0);
+    assert_eq!(
+        check(tcgetattr(pipe_fd[0], &mut termios)),
+        Err(Errno::ENOTTY)
+    );
+    close(pipe_fd[0]);
+    close(pipe_fd[1]);
+    println!("termios passed!");
+    0
+}
@@ -0,0 +1,68 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::errno::{check, Errno};
+use user_lib::*;
+
+const THREADS: usize = 4;
+const ROUNDS: usize = 1000;
+
+static RESULTS: [AtomicUsize; THREADS] = [const { AtomicUsize::new(0) }; THREADS];
+
+/// Sum `1..=ROUNDS` on the stack of the thread.
+fn worker(idx: usize) -> ! {
+    let mut local = [0usize; 64];
+    for i in 1..=ROUNDS {
+        local[i % 64] += i;
+        if i % 100 == 0 {
+            yield_();
+        }
+    }
+    RESULTS[idx].store(local.iter().sum(), Ordering::SeqCst);
+    exit(gettid() as i32)
+}
+
+fn spin(_arg: usize) -> ! {
+    loop {
+        yield_();
+    }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(gettid(), 0);
+    let mut tids = [0usize; THREADS];
+    for (idx, tid) in tids.iter_mut().enumerate() {
+        *tid = check(thread_create(worker as usize, idx)).unwrap();
+        assert_ne!(*tid, 0);
+    }
+    for (idx, tid) in tids.iter().enumerate() {
+        let mut exit_code = -1;
+        assert_eq!(waittid(*tid, &mut exit_code), *tid as isize);
+        assert_eq!(exit_code, *tid as i32);
+        assert_eq!(
+            RESULTS[idx].load(Ordering::SeqCst),
+            ROUNDS * (ROUNDS + 1) / 2
+        );
+    }
+    let mut exit_code = 0;
+    assert_eq!(check(waittid(0, &mut exit_code)), Err(Errno::EDEADLK));
+    assert_eq!(check(waittid(tids[0], &mut exit_code)), Err(Errno::ESRCH));
+
+    // the process exits with the main thread, even if other threads run
+    let pid = fork();
+    if pid == 0 {
+        assert!(thread_create(spin as usize, 0) > 0);
+        yield_();
+        exit(7);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 7);
+    println!("threads passed!");
+    0
+}
@@ -4,7 +4,12 @@
 #[macro_use]
 extern crate user_lib;
 
-use user_lib::{exec, fork, get_time, kill, waitpid, waitpid_nb, SignalFlags};
+use user_lib::{
+    exec, fork, get_time, kill, setitimer, sigaction, waitpid, ITimerVal, SignalAction,
+    SignalFlags, TimeVal, ITIMER_REAL, SIGALRM,
+};
+
+fn on_alarm() {}
 
 #[no_mangle]
 pub fn main(argc: usize, argv: &[&str]) -> i32 {
@@ -20,24 +25,24 @@ pub fn main(argc: usize, argv: &[&str]) -> i32 {
         }
     } else {
         let start_time = get_time();
-        let mut child_exited = false;
+        let mut action = SignalAction::default();
+        action.handler = on_alarm as usize;
+        sigaction(SIGALRM, Some(&action), None);
+        let mut timer = ITimerVal::default();
+        timer.value = TimeVal::from_ms(timeout_ms as usize);
+        setitimer(ITIMER_REAL, &timer, None);
+        // the alarm interrupts the wait
         let mut exit_code: i32 = 0;
-        loop {
-            if get_time() - start_time > timeout_ms {
-                break;
-            }
-            if waitpid_nb(pid, &mut exit_code) as usize == pid {
-                child_exited = true;
-                println!(
-                    "child exited in {}ms, exit_code = {}",
-                    get_time() - start_time,
-                    exit_code,
-                );
-            }
-        }
-        if !child_exited {
+        if waitpid(pid, &mut exit_code) as usize == pid {
+            setitimer(ITIMER_REAL, &ITimerVal::default(), None);
+            println!(
+                "child exited in {}ms, exit_code = {}",
+                get_time() - start_time,
+                exit_code,
+            );
+        } else {
             println!("child has run for {}ms, kill it!", timeout_ms);
-            kill(pid, SignalFlags::SIGINT.bits());
+            kill(pid, SignalFlags::SIGINT.bits() as i32);
             assert_eq!(waitpid(pid, &mut exit_code) as usize, pid);
             println!("exit code of the child is {}", exit_code);
         }
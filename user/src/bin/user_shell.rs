@@ -8,15 +8,19 @@ extern crate alloc;
 extern crate user_lib;
 
 const LF: u8 = 0x0au8;
-const CR: u8 = 0x0du8;
-const DL: u8 = 0x7fu8;
-const BS: u8 = 0x08u8;
 const LINE_START: &str = ">> ";
 
+use alloc::format;
 use alloc::string::String;
 use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, Ordering};
 use user_lib::console::getchar;
-use user_lib::{close, dup, exec, fork, open, pipe, waitpid, OpenFlags};
+use user_lib::errno::{check, Errno};
+use user_lib::{
+    close, dup, exec, fork, getpgid, killpg, open, pipe, setpgid, sigaction, sigreturn, tcgetattr,
+    tcsetattr, tcsetpgrp, waitpid_options, wifstopped, OpenFlags, SignalAction, Termios, SIGCHLD,
+    SIGCONT, SIGINT, SIGTSTP, TCSANOW, WNOHANG, WUNTRACED,
+};
 
 #[derive(Debug)]
 struct ProcessArguments {
@@ -74,18 +78,216 @@ impl ProcessArguments {
     }
 }
 
+/// A pipeline started by the shell, which runs in a process group of its own.
+struct Job {
+    id: usize,
+    pgid: usize,
+    /// Processes which have not exited yet.
+    pids: Vec<usize>,
+    command: String,
+    stopped: bool,
+}
+
+struct Jobs {
+    jobs: Vec<Job>,
+    /// Process group of the shell, which gets the console back.
+    shell_pgid: usize,
+    /// Console attributes of the shell, restored after each foreground job.
+    termios: Termios,
+}
+
+impl Jobs {
+    fn new() -> Self {
+        let mut termios = Termios::default();
+        tcgetattr(0, &mut termios);
+        Self {
+            jobs: Vec::new(),
+            shell_pgid: getpgid(0) as usize,
+            termios,
+        }
+    }
+    fn add(&mut self, pgid: usize, pids: Vec<usize>, command: &str) -> usize {
+        let id = (1..)
+            .find(|id| self.jobs.iter().all(|job| job.id != *id))
+            .unwrap();
+        self.jobs.push(Job {
+            id,
+            pgid,
+            pids,
+            command: String::from(command),
+            stopped: false,
+        });
+        id
+    }
+    /// The job numbered by `arg` such as `2` or `%2`, the latest one if
+    /// `arg` is empty.
+    fn find(&self, arg: &str) -> Option<usize> {
+        let arg = arg.trim_start_matches('%');
+        if arg.is_empty() {
+            return self.jobs.len().checked_sub(1);
+        }
+        let id: usize = arg.parse().ok()?;
+        self.jobs.iter().position(|job| job.id == id)
+    }
+    /// A child has exited or stopped, return the index of its job.
+    fn update(&mut self, pid: usize, status: i32) -> Option<usize> {
+        let idx = self.jobs.iter().position(|job| job.pids.contains(&pid))?;
+        let job = &mut self.jobs[idx];
+        if wifstopped(status) {
+            job.stopped = true;
+        } else {
+            job.pids.retain(|p| *p != pid);
+        }
+        Some(idx)
+    }
+    /// Give the console to job `idx` and wait until it exits or stops.
+    fn wait_foreground(&mut self, idx: usize) {
+        let pgid = self.jobs[idx].pgid;
+        tcsetpgrp(0, pgid);
+        while let Some(&pid) = self.jobs[idx].pids.first() {
+            let mut status = 0;
+            let ret = waitpid_options(pid as isize, &mut status, WUNTRACED);
+            // SIGCHLD of a background job interrupts the wait
+            if ret == -(Errno::EINTR as isize) {
+                continue;
+            }
+            if ret != pid as isize {
+                break;
+            }
+            self.update(pid, status);
+            // the console echoed ^C without a newline
+            if status == -SIGINT {
+                println!("");
+            }
+            if self.jobs[idx].stopped {
+                let job = &self.jobs[idx];
+                println!("");
+                println!("[{}]+  Stopped    {}", job.id, job.command);
+                break;
+            }
+        }
+        tcsetpgrp(0, self.shell_pgid);
+        tcsetattr(0, TCSANOW, &self.termios);
+        if self.jobs[idx].pids.is_empty() {
+            self.jobs.remove(idx);
+        }
+    }
+    /// Collect the background jobs which have exited or stopped, return the
+    /// notices to print about them.
+    fn reap(&mut self) -> Vec<String> {
+        let mut notices = Vec::new();
+        loop {
+            let mut status = 0;
+            let pid = waitpid_options(-1, &mut status, WNOHANG | WUNTRACED);
+            if pid <= 0 {
+                break;
+            }
+            // orphans are adopted by the shell, they are not our jobs
+            let Some(idx) = self.update(pid as usize, status) else {
+                continue;
+            };
+            let job = &self.jobs[idx];
+            if job.pids.is_empty() {
+                notices.push(format!("[{}]+  Done       {}", job.id, job.command));
+                self.jobs.remove(idx);
+            } else if wifstopped(status) {
+                notices.push(format!("[{}]+  Stopped    {}", job.id, job.command));
+            }
+        }
+        notices
+    }
+    /// Run the built-in command `jobs`, `fg` or `bg`, return false if
+    /// `line` is not one.
+    fn builtin(&mut self, line: &str) -> bool {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let arg = words.next().unwrap_or("");
+        match command {
+            "jobs" => {
+                for job in self.jobs.iter() {
+                    let state = if job.stopped { "Stopped" } else { "Running" };
+                    println!("[{}]   {:<10} {}", job.id, state, job.command);
+                }
+            }
+            "fg" | "bg" => {
+                let Some(idx) = self.find(arg) else {
+                    println!("{}: no such job", command);
+                    return true;
+                };
+                let job = &mut self.jobs[idx];
+                job.stopped = false;
+                if command == "fg" {
+                    println!("{}", job.command);
+                    // take the console before it runs again
+                    tcsetpgrp(0, job.pgid);
+                    killpg(job.pgid, SIGCONT);
+                    self.wait_foreground(idx);
+                } else {
+                    println!("[{}]+ {} &", job.id, job.command);
+                    killpg(job.pgid, SIGCONT);
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Set when a child has exited or stopped, the jobs are collected once the
+/// shell is back to its main loop.
+static CHILD_CHANGED: AtomicBool = AtomicBool::new(false);
+
+fn child_changed() {
+    CHILD_CHANGED.store(true, Ordering::SeqCst);
+    sigreturn();
+}
+
+/// ^C and ^Z at the prompt only discard the line being typed.
+fn interrupted() {
+    println!("");
+    print!("{}", LINE_START);
+    sigreturn();
+}
+
 #[no_mangle]
 pub fn main() -> i32 {
     println!("Rust user shell");
+    let mut action = SignalAction::default();
+    action.handler = interrupted as usize;
+    sigaction(SIGINT, Some(&action), None);
+    sigaction(SIGTSTP, Some(&action), None);
+    action.handler = child_changed as usize;
+    sigaction(SIGCHLD, Some(&action), None);
+    let mut jobs = Jobs::new();
+    // read the console as its foreground group
+    tcsetpgrp(0, jobs.shell_pgid);
     let mut line: String = String::new();
     print!("{}", LINE_START);
     loop {
+        // the console echoes and edits the line, which arrives once complete
         let c = getchar();
         match c {
-            LF | CR => {
-                println!("");
-                if !line.is_empty() {
-                    let splited: Vec<_> = line.as_str().split('|').collect();
+            // end of file or a read interrupted by a signal
+            0 => {
+                // a background job has finished while the shell waits for input
+                if CHILD_CHANGED.swap(false, Ordering::SeqCst) {
+                    let notices = jobs.reap();
+                    if !notices.is_empty() {
+                        println!("");
+                        for notice in notices {
+                            println!("{}", notice);
+                        }
+                        print!("{}", LINE_START);
+                    }
+                }
+            }
+            LF => {
+                // a trailing `&` runs the command in the background
+                let trimmed = line.trim_end();
+                let background = trimmed.ends_with('&');
+                let command = String::from(trimmed.trim_end_matches('&').trim());
+                if !command.is_empty() && !jobs.builtin(command.as_str()) {
+                    let splited: Vec<_> = command.as_str().split('|').collect();
                     let process_arguments_list: Vec<_> = splited
                         .iter()
                         .map(|&cmd| ProcessArguments::new(cmd))
@@ -121,36 +323,48 @@ pub fn main() -> i32 {
                             }
                         }
                         let mut children: Vec<_> = Vec::new();
+                        // the first process leads the group of the job
+                        let mut pgid = 0;
                         for (i, process_argument) in process_arguments_list.iter().enumerate() {
                             let pid = fork();
                             if pid == 0 {
+                                setpgid(0, pgid);
                                 let input = &process_argument.input;
                                 let output = &process_argument.output;
                                 let args_copy = &process_argument.args_copy;
                                 let args_addr = &process_argument.args_addr;
                                 // redirect input
                                 if !input.is_empty() {
-                                    let input_fd = open(input.as_str(), OpenFlags::RDONLY);
-                                    if input_fd == -1 {
-                                        println!("Error when opening file {}", input);
-                                        return -4;
-                                    }
-                                    let input_fd = input_fd as usize;
+                                    let input_fd =
+                                        match check(open(input.as_str(), OpenFlags::RDONLY)) {
+                                            Ok(fd) => fd,
+                                            Err(errno) => {
+                                                println!(
+                                                    "Error when opening file {}: {:?}",
+                                                    input, errno
+                                                );
+                                                return -4;
+                                            }
+                                        };
                                     close(0);
                                     assert_eq!(dup(input_fd), 0);
                                     close(input_fd);
                                 }
                                 // redirect output
                                 if !output.is_empty() {
-                                    let output_fd = open(
+                                    let output_fd = match check(open(
                                         output.as_str(),
                                         OpenFlags::CREATE | OpenFlags::WRONLY,
-                                    );
-                                    if output_fd == -1 {
-                                        println!("Error when opening file {}", output);
-                                        return -4;
-                                    }
-                                    let output_fd = output_fd as usize;
+                                    )) {
+                                        Ok(fd) => fd,
+                                        Err(errno) => {
+                                            println!(
+                                                "Error when opening file {}: {:?}",
+                                                output, errno
+                                            );
+                                            return -4;
+                                        }
+                                    };
                                     close(1);
                                     assert_eq!(dup(output_fd), 1);
                                     close(output_fd);
@@ -173,40 +387,41 @@ pub fn main() -> i32 {
                                     close(pipe_fd[1]);
                                 }
                                 // execute new application
-                                if exec(args_copy[0].as_str(), args_addr.as_slice()) == -1 {
-                                    println!("Error when executing!");
-                                    return -4;
-                                }
-                                unreachable!();
+                                let errno =
+                                    check(exec(args_copy[0].as_str(), args_addr.as_slice()))
+                                        .unwrap_err();
+                                println!("Error when executing: {:?}", errno);
+                                return -4;
                             } else {
-                                children.push(pid);
+                                // also set it here, whoever runs first
+                                if pgid == 0 {
+                                    pgid = pid as usize;
+                                }
+                                setpgid(pid as usize, pgid);
+                                children.push(pid as usize);
                             }
                         }
                         for pipe_fd in pipes_fd.iter() {
                             close(pipe_fd[0]);
                             close(pipe_fd[1]);
                         }
-                        let mut exit_code: i32 = 0;
-                        for pid in children.into_iter() {
-                            let exit_pid = waitpid(pid as usize, &mut exit_code);
-                            assert_eq!(pid, exit_pid);
-                            //println!("Shell: Process {} exited with code {}", pid, exit_code);
+                        let id = jobs.add(pgid, children, command.as_str());
+                        if background {
+                            println!("[{}] {}", id, pgid);
+                        } else {
+                            let idx = jobs.jobs.len() - 1;
+                            jobs.wait_foreground(idx);
                         }
                     }
-                    line.clear();
                 }
-                print!("{}", LINE_START);
-            }
-            BS | DL => {
-                if !line.is_empty() {
-                    print!("{}", BS as char);
-                    print!(" ");
-                    print!("{}", BS as char);
-                    line.pop();
+                line.clear();
+                CHILD_CHANGED.store(false, Ordering::SeqCst);
+                for notice in jobs.reap() {
+                    println!("{}", notice);
                 }
+                print!("{}", LINE_START);
             }
             _ => {
-                print!("{}", c as char);
                 line.push(c as char);
             }
         }
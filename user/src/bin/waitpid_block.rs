@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{check, Errno};
+use user_lib::{exit, fork, waitpid, waitpid_nb, yield_};
+
+const MAGIC: i32 = 0x2333;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        for _ in 0..10 {
+            yield_();
+        }
+        exit(MAGIC);
+    }
+    let mut exit_code: i32 = 0;
+    // the child is still yielding
+    assert_eq!(waitpid_nb(pid as usize, &mut exit_code), 0);
+    // sleep until the child exits
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, MAGIC);
+    assert_eq!(
+        check(waitpid_nb(pid as usize, &mut exit_code)),
+        Err(Errno::ECHILD)
+    );
+    println!("waitpid_block passed!");
+    0
+}
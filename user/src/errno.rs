@@ -0,0 +1,55 @@
+/// Linux error numbers, as returned negated by the kernel.
+#[allow(clippy::upper_case_acronyms)]
+#[repr(isize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Errno {
+    EPERM = 1,
+    ENOENT = 2,
+    ESRCH = 3,
+    EINTR = 4,
+    EIO = 5,
+    E2BIG = 7,
+    ENOEXEC = 8,
+    EBADF = 9,
+    ECHILD = 10,
+    EAGAIN = 11,
+    ENOMEM = 12,
+    EFAULT = 14,
+    EBUSY = 16,
+    EEXIST = 17,
+    EINVAL = 22,
+    EMFILE = 24,
+    ENOTTY = 25,
+    ESPIPE = 29,
+    EPIPE = 32,
+    EDEADLK = 35,
+    ENOSYS = 38,
+    ETIMEDOUT = 110,
+}
+
+pub type SysResult<T = usize> = Result<T, Errno>;
+
+impl Errno {
+    /// Look up the error number returned by a failed syscall.
+    pub fn from_raw(errno: isize) -> Option<Self> {
+        use Errno::*;
+        const ALL: [Errno; 22] = [
+            EPERM, ENOENT, ESRCH, EINTR, EIO, E2BIG, ENOEXEC, EBADF, ECHILD, EAGAIN, ENOMEM,
+            EFAULT, EBUSY, EEXIST, EINVAL, EMFILE, ENOTTY, ESPIPE, EPIPE, EDEADLK, ENOSYS,
+            ETIMEDOUT,
+        ];
+        ALL.iter().copied().find(|e| *e as isize == errno)
+    }
+}
+
+/// Turn the raw return value of a syscall into a `Result`.
+///
+/// Negative values are errors; an error number the kernel doesn't know about
+/// shows up as `EINVAL`.
+pub fn check(ret: isize) -> SysResult {
+    if ret < 0 {
+        Err(Errno::from_raw(-ret).unwrap_or(Errno::EINVAL))
+    } else {
+        Ok(ret as usize)
+    }
+}
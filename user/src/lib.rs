@@ -5,7 +5,9 @@
 
 #[macro_use]
 pub mod console;
+pub mod errno;
 mod lang_items;
+pub mod sync;
 mod syscall;
 
 extern crate alloc;
@@ -13,16 +15,58 @@ extern crate alloc;
 extern crate bitflags;
 
 use alloc::vec::Vec;
-use buddy_system_allocator::LockedHeap;
+use buddy_system_allocator::{Heap, LockedHeap};
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::NonNull;
+use core::sync::atomic::AtomicU32;
 use syscall::*;
 
 const USER_HEAP_SIZE: usize = 32768;
+const PAGE_SIZE: usize = 0x1000;
 
 static mut HEAP_SPACE: [u8; USER_HEAP_SIZE] = [0; USER_HEAP_SIZE];
 
-#[global_allocator]
 static HEAP: LockedHeap = LockedHeap::empty();
 
+/// Heap which starts with `HEAP_SPACE` and grows through `sbrk` once it
+/// runs out of memory.
+struct GrowableHeap;
+
+#[global_allocator]
+static ALLOCATOR: GrowableHeap = GrowableHeap;
+
+/// Extend the heap with enough memory to serve `layout`.
+fn grow_heap(heap: &mut Heap, layout: &Layout) -> bool {
+    // any range of twice the block size holds an aligned block
+    let size = 2 * layout.size().max(layout.align()).next_power_of_two();
+    let size = (size + PAGE_SIZE - 1) / PAGE_SIZE * PAGE_SIZE;
+    let start = sbrk(size as isize);
+    if start < 0 {
+        return false;
+    }
+    unsafe {
+        heap.add_to_heap(start as usize, start as usize + size);
+    }
+    true
+}
+
+unsafe impl GlobalAlloc for GrowableHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut heap = HEAP.lock();
+        loop {
+            if let Ok(ptr) = heap.alloc(layout) {
+                return ptr.as_ptr();
+            }
+            if !grow_heap(&mut heap, &layout) {
+                return core::ptr::null_mut();
+            }
+        }
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        HEAP.lock().dealloc(NonNull::new_unchecked(ptr), layout)
+    }
+}
+
 #[alloc_error_handler]
 pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
     panic!("Heap allocation error, layout = {:?}", layout);
@@ -104,38 +148,402 @@ pub fn fork() -> isize {
 pub fn exec(path: &str, args: &[*const u8]) -> isize {
     sys_exec(path, args)
 }
+/// Return immediately from `waitpid` if no child has exited yet.
+pub const WNOHANG: u32 = 1;
+/// Report stopped children too.
+pub const WUNTRACED: u32 = 2;
+/// Report children continued by SIGCONT too.
+pub const WCONTINUED: u32 = 8;
+
+/// Whether a status from `waitpid_options` tells a stop rather than an exit
+/// code. Exit codes are not encoded, so some rare ones look the same.
+pub fn wifstopped(status: i32) -> bool {
+    status & 0xff == 0x7f && (1..=31).contains(&(status >> 8))
+}
+/// The signal which has stopped the child.
+pub fn wstopsig(status: i32) -> i32 {
+    status >> 8
+}
+/// Whether a status from `waitpid_options` tells the child has continued.
+pub fn wifcontinued(status: i32) -> bool {
+    status == 0xffff
+}
+
 pub fn wait(exit_code: &mut i32) -> isize {
-    loop {
-        match sys_waitpid(-1, exit_code as *mut _) {
-            -2 => {
-                yield_();
-            }
-            // -1 or a real pid
-            exit_pid => return exit_pid,
+    sys_waitpid(-1, exit_code as *mut _, 0)
+}
+
+pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
+    sys_waitpid(pid as isize, exit_code as *mut _, 0)
+}
+
+/// Like `waitpid`, but return 0 if the child is still running.
+pub fn waitpid_nb(pid: usize, exit_code: &mut i32) -> isize {
+    sys_waitpid(pid as isize, exit_code as *mut _, WNOHANG)
+}
+
+/// `waitpid` with `WNOHANG`, `WUNTRACED` and `WCONTINUED` options. `pid` -1 means any child.
+pub fn waitpid_options(pid: isize, status: &mut i32, options: u32) -> isize {
+    sys_waitpid(pid, status as *mut _, options)
+}
+
+pub fn setpgid(pid: usize, pgid: usize) -> isize {
+    sys_setpgid(pid, pgid)
+}
+pub fn getpgid(pid: usize) -> isize {
+    sys_getpgid(pid)
+}
+pub fn getsid(pid: usize) -> isize {
+    sys_getsid(pid)
+}
+pub fn setsid() -> isize {
+    sys_setsid()
+}
+
+const TCGETS: usize = 0x5401;
+const TCSETS: usize = 0x5402;
+const TCSETSW: usize = 0x5403;
+const TCSETSF: usize = 0x5404;
+const TIOCGPGRP: usize = 0x540f;
+const TIOCSPGRP: usize = 0x5410;
+
+/// `struct termios` of Linux.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Termios {
+    pub iflag: u32,
+    pub oflag: u32,
+    pub cflag: u32,
+    pub lflag: u32,
+    pub line: u8,
+    pub cc: [u8; 19],
+}
+
+pub const ICRNL: u32 = 0o400;
+pub const ISIG: u32 = 0o1;
+pub const ICANON: u32 = 0o2;
+pub const ECHO: u32 = 0o10;
+pub const ECHOE: u32 = 0o20;
+pub const ECHOK: u32 = 0o40;
+pub const ECHOCTL: u32 = 0o1000;
+
+pub const VINTR: usize = 0;
+pub const VQUIT: usize = 1;
+pub const VERASE: usize = 2;
+pub const VKILL: usize = 3;
+pub const VEOF: usize = 4;
+pub const VTIME: usize = 5;
+pub const VMIN: usize = 6;
+pub const VSUSP: usize = 10;
+
+/// When `tcsetattr` takes effect: `TCSAFLUSH` also discards the pending
+/// input.
+pub const TCSANOW: usize = 0;
+pub const TCSADRAIN: usize = 1;
+pub const TCSAFLUSH: usize = 2;
+
+/// Get the attributes of terminal `fd`.
+pub fn tcgetattr(fd: usize, termios: &mut Termios) -> isize {
+    sys_ioctl(fd, TCGETS, termios as *mut Termios as usize)
+}
+/// Set the attributes of terminal `fd`.
+pub fn tcsetattr(fd: usize, action: usize, termios: &Termios) -> isize {
+    let request = match action {
+        TCSANOW => TCSETS,
+        TCSADRAIN => TCSETSW,
+        TCSAFLUSH => TCSETSF,
+        _ => return -(errno::Errno::EINVAL as isize),
+    };
+    sys_ioctl(fd, request, termios as *const Termios as usize)
+}
+
+/// The foreground process group of terminal `fd`.
+pub fn tcgetpgrp(fd: usize) -> isize {
+    let mut pgid = 0i32;
+    match sys_ioctl(fd, TIOCGPGRP, &mut pgid as *mut i32 as usize) {
+        0 => pgid as isize,
+        err => err,
+    }
+}
+/// Give terminal `fd` to process group `pgid`.
+pub fn tcsetpgrp(fd: usize, pgid: usize) -> isize {
+    let pgid = pgid as i32;
+    sys_ioctl(fd, TIOCSPGRP, &pgid as *const i32 as usize)
+}
+
+/// Start a thread running `entry(arg)` on a new stack, return its tid.
+/// The thread must call `exit` instead of returning from `entry`.
+pub fn thread_create(entry: usize, arg: usize) -> isize {
+    sys_thread_create(entry, arg)
+}
+pub fn gettid() -> isize {
+    sys_gettid()
+}
+/// Wait for thread `tid` to exit and release it.
+pub fn waittid(tid: usize, exit_code: &mut i32) -> isize {
+    sys_waittid(tid, exit_code as *mut _)
+}
+
+/// Create a kernel mutex, return its id.
+pub fn mutex_create() -> isize {
+    sys_mutex_create()
+}
+pub fn mutex_lock(id: usize) -> isize {
+    sys_mutex_lock(id)
+}
+pub fn mutex_unlock(id: usize) -> isize {
+    sys_mutex_unlock(id)
+}
+/// Create a kernel semaphore with `count` units, return its id.
+pub fn semaphore_create(count: usize) -> isize {
+    sys_semaphore_create(count)
+}
+pub fn semaphore_up(id: usize) -> isize {
+    sys_semaphore_up(id)
+}
+pub fn semaphore_down(id: usize) -> isize {
+    sys_semaphore_down(id)
+}
+/// Create a kernel condition variable, return its id.
+pub fn condvar_create() -> isize {
+    sys_condvar_create()
+}
+pub fn condvar_signal(id: usize) -> isize {
+    sys_condvar_signal(id)
+}
+/// Unlock mutex `mutex_id`, wait on condvar `id` and lock the mutex again.
+pub fn condvar_wait(id: usize, mutex_id: usize) -> isize {
+    sys_condvar_wait(id, mutex_id)
+}
+/// Make `mutex_lock` and `semaphore_down` fail with `EDEADLK` when the
+/// threads of the process may deadlock.
+pub fn enable_deadlock_detect(enabled: bool) -> isize {
+    sys_enable_deadlock_detect(enabled as usize)
+}
+
+pub const FUTEX_WAIT: usize = 0;
+pub const FUTEX_WAKE: usize = 1;
+pub const FUTEX_PRIVATE_FLAG: usize = 128;
+
+/// Block while `word` holds `val`, for at most `timeout` if given.
+pub fn futex_wait(word: &AtomicU32, val: u32, timeout: Option<&TimeSpec>) -> isize {
+    sys_futex(
+        word.as_ptr(),
+        FUTEX_WAIT | FUTEX_PRIVATE_FLAG,
+        val,
+        timeout.map_or(core::ptr::null(), |timeout| timeout as *const _),
+    )
+}
+/// Wake up at most `count` threads blocked on `word`, return how many are woken.
+pub fn futex_wake(word: &AtomicU32, count: u32) -> isize {
+    sys_futex(
+        word.as_ptr(),
+        FUTEX_WAKE | FUTEX_PRIVATE_FLAG,
+        count,
+        core::ptr::null(),
+    )
+}
+
+bitflags! {
+    pub struct MmapProt: usize {
+        const READ = 1 << 0;
+        const WRITE = 1 << 1;
+        const EXEC = 1 << 2;
+    }
+}
+
+pub fn brk(addr: usize) -> isize {
+    sys_brk(addr)
+}
+/// Move the program break by `increment` bytes and return the old break.
+pub fn sbrk(increment: isize) -> isize {
+    let old_brk = sys_brk(0);
+    if increment == 0 {
+        return old_brk;
+    }
+    let new_brk = old_brk + increment;
+    if sys_brk(new_brk as usize) != new_brk {
+        return -1;
+    }
+    old_brk
+}
+
+pub fn mmap(start: usize, len: usize, prot: MmapProt) -> isize {
+    sys_mmap(start, len, prot.bits)
+}
+pub fn munmap(start: usize, len: usize) -> isize {
+    sys_munmap(start, len)
+}
+pub fn mprotect(start: usize, len: usize, prot: MmapProt) -> isize {
+    sys_mprotect(start, len, prot.bits)
+}
+
+pub const PRIO_PROCESS: usize = 0;
+
+/// Set the nice value of process `who`, or the caller if it is 0.
+pub fn setpriority(which: usize, who: usize, nice: i32) -> isize {
+    sys_setpriority(which, who, nice)
+}
+/// Like the raw syscall of Linux, return `20 - nice` on success.
+pub fn getpriority(which: usize, who: usize) -> isize {
+    sys_getpriority(which, who)
+}
+
+pub const SCHED_OTHER: usize = 0;
+pub const SCHED_BATCH: usize = 3;
+pub const SCHED_IDLE: usize = 5;
+
+pub fn sched_setscheduler(pid: usize, policy: usize, priority: i32) -> isize {
+    sys_sched_setscheduler(pid, policy, &priority)
+}
+pub fn sched_getscheduler(pid: usize) -> isize {
+    sys_sched_getscheduler(pid)
+}
+
+pub const CLOCK_REALTIME: usize = 0;
+pub const CLOCK_MONOTONIC: usize = 1;
+/// `req` of `clock_nanosleep` is an absolute time instead of a duration.
+pub const TIMER_ABSTIME: usize = 1;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+impl TimeSpec {
+    pub fn from_ms(ms: usize) -> Self {
+        Self {
+            sec: ms / 1000,
+            nsec: ms % 1000 * 1_000_000,
         }
     }
 }
 
-pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
-    loop {
-        match sys_waitpid(pid as isize, exit_code as *mut _) {
-            -2 => {
-                yield_();
-            }
-            // -1 or a real pid
-            exit_pid => return exit_pid,
+/// Sleep for `req`. If interrupted by a signal, the time left is stored in `rem`.
+pub fn nanosleep(req: &TimeSpec, rem: Option<&mut TimeSpec>) -> isize {
+    sys_nanosleep(req, rem.map_or(core::ptr::null_mut(), |rem| rem as *mut _))
+}
+pub fn clock_nanosleep(
+    clock_id: usize,
+    flags: usize,
+    req: &TimeSpec,
+    rem: Option<&mut TimeSpec>,
+) -> isize {
+    sys_clock_nanosleep(
+        clock_id,
+        flags,
+        req,
+        rem.map_or(core::ptr::null_mut(), |rem| rem as *mut _),
+    )
+}
+
+/// Clock ticks per second of `times`.
+pub const CLOCKS_PER_SEC: usize = 100;
+
+/// Time used by a process and its waited children in clock ticks.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Tms {
+    pub utime: usize,
+    pub stime: usize,
+    pub cutime: usize,
+    pub cstime: usize,
+}
+
+/// Store the time used in `tms`, return the clock ticks since boot.
+pub fn times(tms: &mut Tms) -> isize {
+    sys_times(tms as *mut _)
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeVal {
+    pub sec: usize,
+    pub usec: usize,
+}
+
+impl TimeVal {
+    pub fn from_ms(ms: usize) -> Self {
+        Self {
+            sec: ms / 1000,
+            usec: ms % 1000 * 1000,
         }
     }
+    pub fn to_us(&self) -> usize {
+        self.sec * 1_000_000 + self.usec
+    }
 }
 
-pub fn waitpid_nb(pid: usize, exit_code: &mut i32) -> isize {
-    sys_waitpid(pid as isize, exit_code as *mut _)
+/// The interval timers of a process, which raise `SIGALRM` in real time,
+/// `SIGVTALRM` in user time, and `SIGPROF` in user and system time.
+pub const ITIMER_REAL: usize = 0;
+pub const ITIMER_VIRTUAL: usize = 1;
+pub const ITIMER_PROF: usize = 2;
+
+/// Time left before an interval timer expires, and its interval.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ITimerVal {
+    pub interval: TimeVal,
+    pub value: TimeVal,
 }
 
+/// Set the interval timer `which`, or disarm it with a zero value, and get
+/// the previous one.
+pub fn setitimer(which: usize, value: &ITimerVal, old_value: Option<&mut ITimerVal>) -> isize {
+    sys_setitimer(which, value, old_value.map_or(core::ptr::null_mut(), |v| v))
+}
+
+pub fn getitimer(which: usize, value: &mut ITimerVal) -> isize {
+    sys_getitimer(which, value)
+}
+
+/// Send `SIGALRM` after `seconds`, or cancel the alarm if it is 0. Return
+/// the seconds which were left before the previous alarm, rounded.
+pub fn alarm(seconds: usize) -> usize {
+    let value = ITimerVal {
+        interval: TimeVal::default(),
+        value: TimeVal {
+            sec: seconds,
+            usec: 0,
+        },
+    };
+    let mut old_value = ITimerVal::default();
+    setitimer(ITIMER_REAL, &value, Some(&mut old_value));
+    let old = old_value.value;
+    // an alarm about to go off is not 0
+    if old.usec >= 500_000 || (old.sec == 0 && old.usec > 0) {
+        old.sec + 1
+    } else {
+        old.sec
+    }
+}
+
+pub const RUSAGE_SELF: isize = 0;
+pub const RUSAGE_CHILDREN: isize = -1;
+pub const RUSAGE_THREAD: isize = 1;
+
+/// Resource usage, of which only the times are counted.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rusage {
+    pub utime: TimeVal,
+    pub stime: TimeVal,
+    pub others: [isize; 14],
+}
+
+pub fn getrusage(who: isize, usage: &mut Rusage) -> isize {
+    sys_getrusage(who, usage as *mut _)
+}
+
+/// Sleep for `period_ms` milliseconds, even if signals arrive in between.
 pub fn sleep(period_ms: usize) {
-    let start = sys_get_time();
-    while sys_get_time() < start + period_ms as isize {
-        sys_yield();
+    let mut req = TimeSpec::from_ms(period_ms);
+    let mut rem = TimeSpec::default();
+    while nanosleep(&req, Some(&mut rem)) == -(errno::Errno::EINTR as isize) {
+        req = rem;
     }
 }
 
@@ -145,6 +553,9 @@ pub fn sleep(period_ms: usize) {
 pub struct SignalAction {
     pub handler: usize,
     pub mask: SignalFlags,
+    pub flags: u32,
+    /// Where the handler returns with `SA_RESTORER`.
+    pub restorer: usize,
 }
 
 impl Default for SignalAction {
@@ -152,10 +563,111 @@ impl Default for SignalAction {
         Self {
             handler: 0,
             mask: SignalFlags::empty(),
+            flags: 0,
+            restorer: 0,
+        }
+    }
+}
+
+/// Flags of `SignalAction`. With `SA_SIGINFO` the handler is an
+/// `extern "C" fn(i32, &SigInfo, &mut UContext)`.
+pub const SA_SIGINFO: u32 = 4;
+pub const SA_RESTORER: u32 = 0x0400_0000;
+pub const SA_ONSTACK: u32 = 0x0800_0000;
+pub const SA_RESTART: u32 = 0x1000_0000;
+pub const SA_NODEFER: u32 = 0x4000_0000;
+
+/// `SigInfo::code` of a signal sent by `kill`, or by the kernel.
+pub const SI_USER: i32 = 0;
+pub const SI_KERNEL: i32 = 0x80;
+/// `SigInfo::code` of a signal sent by `sigqueue`.
+pub const SI_QUEUE: i32 = -1;
+/// `SigInfo::code` of SIGCHLD.
+pub const CLD_EXITED: i32 = 1;
+pub const CLD_KILLED: i32 = 2;
+pub const CLD_STOPPED: i32 = 5;
+pub const CLD_CONTINUED: i32 = 6;
+/// `SigInfo::code` of SIGSEGV: the address is not mapped, or doesn't allow
+/// the access.
+pub const SEGV_MAPERR: i32 = 1;
+pub const SEGV_ACCERR: i32 = 2;
+/// `SigInfo::code` of SIGILL.
+pub const ILL_ILLOPC: i32 = 1;
+
+/// Why a signal was sent, passed to handlers with `SA_SIGINFO`.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SigInfo {
+    pub signo: i32,
+    pub errno: i32,
+    pub code: i32,
+    _pad: i32,
+    fields: [usize; 14],
+}
+
+impl SigInfo {
+    /// The sender of `kill` or `sigqueue`, or the child of SIGCHLD.
+    pub fn pid(&self) -> usize {
+        self.fields[0] as u32 as usize
+    }
+    /// The exit code or the signal of the child of SIGCHLD.
+    pub fn status(&self) -> i32 {
+        self.fields[1] as i32
+    }
+    /// The value given to `sigqueue`.
+    pub fn value(&self) -> usize {
+        self.fields[1]
+    }
+    /// The faulting address, or instruction for SIGILL.
+    pub fn addr(&self) -> usize {
+        self.fields[0]
+    }
+    /// The access which has raised SIGSEGV.
+    pub fn access(&self) -> MmapProt {
+        MmapProt::from_bits_truncate(self.fields[1])
+    }
+}
+
+/// Flags of `SignalStack`: the thread runs on it, or it is disabled.
+pub const SS_ONSTACK: i32 = 1;
+pub const SS_DISABLE: i32 = 2;
+/// The smallest alternate stack.
+pub const MINSIGSTKSZ: usize = 2048;
+
+/// An alternate stack for handlers with `SA_ONSTACK`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SignalStack {
+    pub sp: usize,
+    pub flags: i32,
+    pub size: usize,
+}
+
+impl Default for SignalStack {
+    fn default() -> Self {
+        Self {
+            sp: 0,
+            flags: SS_DISABLE,
+            size: 0,
         }
     }
 }
 
+/// The context a handler returns to. The saved registers follow.
+#[repr(C)]
+#[derive(Debug)]
+pub struct UContext {
+    pub flags: usize,
+    pub link: usize,
+    pub stack: SignalStack,
+    /// The signal mask restored when the handler returns.
+    pub sigmask: u64,
+}
+
+/// Handlers which take the default action of a signal, or discard it.
+pub const SIG_DFL: usize = 0;
+pub const SIG_IGN: usize = 1;
+
 pub const SIGDEF: i32 = 0; // Default signal handling
 pub const SIGHUP: i32 = 1;
 pub const SIGINT: i32 = 2;
@@ -188,9 +700,12 @@ pub const SIGWINCH: i32 = 28;
 pub const SIGIO: i32 = 29;
 pub const SIGPWR: i32 = 30;
 pub const SIGSYS: i32 = 31;
+/// The real-time signals, which are queued each time they are sent.
+pub const SIGRTMIN: i32 = 32;
+pub const SIGRTMAX: i32 = 63;
 
 bitflags! {
-    pub struct SignalFlags: i32 {
+    pub struct SignalFlags: u64 {
         const SIGDEF = 1; // Default signal handling
         const SIGHUP = 1 << 1;
         const SIGINT = 1 << 2;
@@ -223,11 +738,16 @@ bitflags! {
         const SIGIO = 1 << 29;
         const SIGPWR = 1 << 30;
         const SIGSYS = 1 << 31;
+        const SIGRT = !0 << SIGRTMIN;
     }
 }
 
 pub fn kill(pid: usize, signum: i32) -> isize {
-    sys_kill(pid, signum)
+    sys_kill(pid as isize, signum)
+}
+/// Send `signum` to every process in group `pgid`.
+pub fn killpg(pgid: usize, signum: i32) -> isize {
+    sys_kill(-(pgid as isize), signum)
 }
 
 pub fn sigaction(
@@ -242,8 +762,70 @@ pub fn sigaction(
     )
 }
 
-pub fn sigprocmask(mask: u32) -> isize {
-    sys_sigprocmask(mask)
+/// Set the alternate stack of the thread, or disable it with `SS_DISABLE`.
+pub fn sigaltstack(stack: Option<&SignalStack>, old_stack: Option<&mut SignalStack>) -> isize {
+    sys_sigaltstack(
+        stack.map_or(core::ptr::null(), |s| s),
+        old_stack.map_or(core::ptr::null_mut(), |s| s),
+    )
+}
+
+/// Queue `signum` with `value` to process `pid`, which a handler with
+/// `SA_SIGINFO` gets from `SigInfo::value`.
+pub fn sigqueue(pid: usize, signum: i32, value: usize) -> isize {
+    sys_sigqueue(pid, signum, value)
+}
+
+/// How `sigprocmask` changes the mask with its set: add, remove or replace.
+pub const SIG_BLOCK: usize = 0;
+pub const SIG_UNBLOCK: usize = 1;
+pub const SIG_SETMASK: usize = 2;
+
+/// Change the signal mask of the thread with `set` as `how` says, and get
+/// the previous one.
+pub fn sigprocmask(
+    how: usize,
+    set: Option<&SignalFlags>,
+    old_set: Option<&mut SignalFlags>,
+) -> isize {
+    sys_sigprocmask(
+        how,
+        set.map_or(core::ptr::null(), |s| s),
+        old_set.map_or(core::ptr::null_mut(), |s| s),
+    )
+}
+
+/// The signals which are blocked and pending.
+pub fn sigpending(set: &mut SignalFlags) -> isize {
+    sys_sigpending(set)
+}
+
+/// Block until a signal runs a handler with `mask` as the signal mask, then
+/// restore the mask. Always fails with `EINTR`.
+pub fn sigsuspend(mask: &SignalFlags) -> isize {
+    sys_sigsuspend(mask)
+}
+
+/// Block until a signal runs a handler.
+pub fn pause() -> isize {
+    let mut mask = SignalFlags::empty();
+    sigprocmask(SIG_BLOCK, None, Some(&mut mask));
+    sigsuspend(&mask)
+}
+
+/// Take one of the blocked signals in `set` once it is pending, and return
+/// its number. Fails with `EAGAIN` after `timeout`, or `EINTR` if a handler
+/// runs first.
+pub fn sigtimedwait(
+    set: &SignalFlags,
+    info: Option<&mut SigInfo>,
+    timeout: Option<&TimeSpec>,
+) -> isize {
+    sys_sigtimedwait(
+        set,
+        info.map_or(core::ptr::null_mut(), |i| i),
+        timeout.map_or(core::ptr::null(), |t| t),
+    )
 }
 
 pub fn sigreturn() -> isize {